@@ -0,0 +1,236 @@
+//! Perform actions that are specific to the shell.
+use std::{
+    any::Any,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use sctk::reexports::calloop::channel;
+
+use crate::{
+    capabilities::Capabilities,
+    core::{layer_shell, window, Point},
+    futures::futures::{
+        channel::{mpsc, oneshot},
+        stream, StreamExt,
+    },
+    runtime::Task,
+};
+
+/// An action that is handled by the shell itself, instead of the runtime.
+#[derive(Debug)]
+pub enum Action {
+    /// Overrides the theme of a window, or clears the override.
+    SetTheme(window::Id, Option<Box<dyn Any + Send>>),
+
+    /// Overrides the scale factor of a window, or clears the override.
+    SetScaleFactor(window::Id, Option<f64>),
+
+    /// Changes the keyboard interactivity of a layer surface.
+    SetKeyboardInteractivity(window::Id, layer_shell::KeyboardInteractivity),
+
+    /// Changes the exclusive zone of a layer surface.
+    SetExclusiveZone(window::Id, crate::layer_shell::ExclusiveZone),
+
+    /// Applies several changes to a layer surface in a single commit.
+    ApplyLayerChanges(window::Id, crate::layer_shell::Changes),
+
+    /// Listens to the raw keysyms of the keyboard.
+    ListenKeysyms(mpsc::UnboundedSender<crate::keysym::Event>),
+
+    /// Listens to the buttons of the pointers, with their timestamps.
+    ListenPointerButtons(mpsc::UnboundedSender<crate::pointer::Event>),
+
+    /// Listens to the files dropped onto the surfaces.
+    ListenDroppedFiles(mpsc::UnboundedSender<crate::dnd::FilesDropped>),
+
+    /// Listens to another client taking over the standard selection.
+    ListenSelectionLost(mpsc::UnboundedSender<()>),
+
+    /// Listens to the compositor shrinking a layer surface.
+    ListenShrinks(
+        window::Id,
+        mpsc::UnboundedSender<crate::layer_shell::Shrink>,
+    ),
+
+    /// Changes the margin of a layer surface.
+    SetMargin(window::Id, crate::core::Padding<i32>),
+
+    /// Moves a layer surface to another layer.
+    SetLayer(
+        window::Id,
+        layer_shell::Layer,
+        oneshot::Sender<Result<(), crate::layer_shell::SetLayerError>>,
+    ),
+
+    /// Queries whether a layer surface holds an exclusive keyboard grab.
+    GetKeyboardGrab(window::Id, oneshot::Sender<bool>),
+
+    /// Queries the output a layer surface is shown on, waiting for the
+    /// surface to enter one if needed.
+    GetOutput(window::Id, oneshot::Sender<Option<String>>),
+
+    /// Queries the modifiers of the focused keyboards.
+    GetModifiers(oneshot::Sender<crate::core::keyboard::Modifiers>),
+
+    /// Queries the font families of the font system.
+    ListFontFamilies(oneshot::Sender<Vec<String>>),
+
+    /// Queries the capabilities of the compositor.
+    GetCapabilities(oneshot::Sender<Capabilities>),
+
+    /// Queries the active renderer.
+    GetRenderer(oneshot::Sender<Option<crate::renderer::Renderer>>),
+
+    /// Confines the pointers over a window to its bounds, or releases them.
+    ConfinePointer(window::Id, bool),
+
+    /// Waits for the next frame of a window to be presented.
+    WaitPresent(window::Id, oneshot::Sender<()>),
+
+    /// Sets the text around the caret of the focused text widget of a window.
+    SetSurroundingText(window::Id, Option<crate::input_method::SurroundingText>),
+
+    /// Commits the pending state of the surface of a window.
+    Commit(window::Id),
+
+    /// Queries whether the frames of a window are throttled.
+    GetThrottled(window::Id, oneshot::Sender<bool>),
+
+    /// Listens to the visibility of a window.
+    ListenVisibility(window::Id, mpsc::UnboundedSender<bool>),
+
+    /// Listens to the dmabuf feedback of a window.
+    #[cfg(feature = "dmabuf-feedback")]
+    ListenDmabufFeedback(window::Id, mpsc::UnboundedSender<crate::dmabuf::Feedback>),
+
+    /// Blurs the background of a window, or stops blurring it.
+    #[cfg(feature = "blur")]
+    SetBlur(window::Id, bool),
+
+    /// Sets the opacity multiplier of a window.
+    SetAlpha(window::Id, f32),
+
+    /// Restricts the input region of a window to its area within a margin.
+    SetInputMargin(window::Id, Option<crate::core::Padding>),
+
+    /// Sets the presentation hint of a window.
+    SetPresentationHint(window::Id, crate::surface::PresentationHint),
+
+    /// Suspends the redraws of a window, until resumed or timed out.
+    SuspendRedraws(window::Id, Duration),
+
+    /// Sets the minimum interval between two redraws of a window.
+    SetMinRedrawInterval(window::Id, Option<Duration>),
+
+    /// Resumes the redraws of a window.
+    ResumeRedraws(window::Id),
+
+    /// Sets the timeout after which the cursor is hidden over a window.
+    SetCursorAutoHide(window::Id, Option<Duration>),
+
+    /// Opens a layer surface, failing if its output does not exist.
+    OpenLayer(
+        window::Id,
+        layer_shell::Settings,
+        oneshot::Sender<Result<window::Id, crate::layer_shell::OpenError>>,
+    ),
+
+    /// Opens a layer surface on every output, including outputs that are
+    /// connected later on.
+    OpenLayerOnAllOutputs(layer_shell::Settings, mpsc::UnboundedSender<window::Id>),
+
+    /// Creates a sub-surface within a window.
+    CreateSubsurface(
+        window::Id,
+        Point<i32>,
+        oneshot::Sender<Result<crate::subsurface::Subsurface, crate::subsurface::CreateError>>,
+    ),
+
+    /// Moves a sub-surface within its parent window.
+    SetSubsurfacePosition(crate::subsurface::Id, Point<i32>),
+
+    /// Destroys a sub-surface.
+    DestroySubsurface(crate::subsurface::Id),
+}
+
+/// The sender of the running shell, if any.
+///
+/// Only a single shell may run in a process at any given time.
+static SENDER: Mutex<Option<channel::Sender<Action>>> = Mutex::new(None);
+
+/// Connects the running shell, replacing any previous one, until the
+/// returned [`Connected`] guard is dropped.
+pub(crate) fn connect(sender: channel::Sender<Action>) -> Connected {
+    *SENDER.lock().unwrap_or_else(PoisonError::into_inner) = Some(sender);
+
+    Connected
+}
+
+/// Disconnects the running shell when dropped, however it stops.
+#[must_use]
+pub(crate) struct Connected;
+
+impl Drop for Connected {
+    fn drop(&mut self) {
+        *SENDER.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+/// Creates a [`Task`] that performs the given shell [`Action`] and produces
+/// no output.
+pub(crate) fn effect<T>(action: Action) -> Task<T>
+where
+    T: Send + 'static,
+{
+    Task::future(async move { dispatch(action) }).discard()
+}
+
+/// Creates a [`Task`] that performs the shell [`Action`] and produces the
+/// value sent back through its channel, if any.
+pub(crate) fn oneshot<T>(f: impl FnOnce(oneshot::Sender<T>) -> Action + Send + 'static) -> Task<T>
+where
+    T: Send + 'static,
+{
+    Task::future(async move {
+        let (sender, receiver) = oneshot::channel();
+
+        dispatch(f(sender));
+
+        receiver.await.ok()
+    })
+    .and_then(Task::done)
+}
+
+/// Creates a [`Task`] that performs the shell [`Action`] and produces every
+/// value sent back through its channel, until the channel is closed.
+pub(crate) fn stream<T>(
+    f: impl FnOnce(mpsc::UnboundedSender<T>) -> Action + Send + 'static,
+) -> Task<T>
+where
+    T: Send + 'static,
+{
+    Task::stream(
+        stream::once(async move {
+            let (sender, receiver) = mpsc::unbounded();
+
+            dispatch(f(sender));
+
+            receiver
+        })
+        .flatten(),
+    )
+}
+
+fn dispatch(action: Action) {
+    let sender = SENDER.lock().unwrap_or_else(PoisonError::into_inner);
+
+    match sender.as_ref() {
+        Some(sender) => {
+            if sender.send(action).is_err() {
+                log::warn!("Shell action dropped: the shell is no longer running.");
+            }
+        }
+        None => log::warn!("Shell action dropped: no shell is running."),
+    }
+}
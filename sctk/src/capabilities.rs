@@ -0,0 +1,90 @@
+//! Query the capabilities of the compositor.
+use sctk::reexports::client::globals::GlobalList;
+
+use crate::{action, runtime::Task};
+
+/// The protocols supported by the compositor, detected at startup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    /// Whether layer surfaces (`zwlr_layer_shell_v1`) can be opened.
+    pub layer_shell: bool,
+    /// Whether normal windows (`xdg_wm_base`) are supported.
+    pub xdg_shell: bool,
+    /// Whether input methods (`zwp_text_input_manager_v3`) are available.
+    pub text_input: bool,
+    /// Whether fractional scaling (`wp_fractional_scale_manager_v1`) is
+    /// supported.
+    pub fractional_scale: bool,
+    /// Whether surfaces can be scaled by the compositor (`wp_viewporter`).
+    pub viewporter: bool,
+    /// Whether the session can be locked (`ext_session_lock_manager_v1`).
+    pub session_lock: bool,
+    /// Whether surfaces can request activation (`xdg_activation_v1`).
+    pub activation: bool,
+    /// Whether the opacity of surfaces can be changed by the compositor
+    /// (`wp_alpha_modifier_v1`).
+    pub alpha_modifier: bool,
+    /// Whether the primary selection (`zwp_primary_selection_device_manager_v1`)
+    /// is supported.
+    pub primary_selection: bool,
+    /// Whether buffers can be presented with explicit synchronization
+    /// (`wp_linux_drm_syncobj_manager_v1`).
+    ///
+    /// The shell does not attach buffers itself: with `wgpu`, presentation
+    /// goes through the window system integration of the graphics driver,
+    /// which uses explicit synchronization on its own when both the driver
+    /// (e.g. Mesa 24.1 or later) and the compositor support it, and falls
+    /// back to implicit synchronization otherwise.
+    ///
+    /// The shell cannot take part in it: a `wl_surface` only accepts a
+    /// single `wp_linux_drm_syncobj_surface_v1`, which the driver creates,
+    /// and the software renderer presents `wl_shm` buffers, which are never
+    /// explicitly synchronized.
+    pub explicit_sync: bool,
+    /// Whether sub-surfaces (`wl_subcompositor`) can be created.
+    pub subsurfaces: bool,
+    /// Whether normal windows can be tagged for the rules of the compositor
+    /// (`xdg_toplevel_tag_manager_v1`).
+    ///
+    /// The tag protocol only applies to normal windows, which the shell does
+    /// not open yet. Layer surfaces are matched by their namespace instead.
+    pub toplevel_tag: bool,
+    /// Whether surfaces can be presented asynchronously, allowing tearing
+    /// (`wp_tearing_control_manager_v1`).
+    pub tearing_control: bool,
+    /// Whether the background of surfaces can be blurred
+    /// (`org_kde_kwin_blur_manager`).
+    ///
+    /// This is always `false` if the `blur` feature is disabled.
+    pub blur: bool,
+}
+
+impl Capabilities {
+    pub(crate) fn detect(globals: &GlobalList) -> Self {
+        globals.contents().with_list(|globals| {
+            let has = |interface: &str| globals.iter().any(|global| global.interface == interface);
+
+            Self {
+                layer_shell: has("zwlr_layer_shell_v1"),
+                xdg_shell: has("xdg_wm_base"),
+                text_input: has("zwp_text_input_manager_v3"),
+                fractional_scale: has("wp_fractional_scale_manager_v1"),
+                viewporter: has("wp_viewporter"),
+                session_lock: has("ext_session_lock_manager_v1"),
+                activation: has("xdg_activation_v1"),
+                alpha_modifier: has("wp_alpha_modifier_v1"),
+                primary_selection: has("zwp_primary_selection_device_manager_v1"),
+                explicit_sync: has("wp_linux_drm_syncobj_manager_v1"),
+                subsurfaces: has("wl_subcompositor"),
+                toplevel_tag: has("xdg_toplevel_tag_manager_v1"),
+                tearing_control: has("wp_tearing_control_manager_v1"),
+                blur: cfg!(feature = "blur") && has("org_kde_kwin_blur_manager"),
+            }
+        })
+    }
+}
+
+/// Fetches the [`Capabilities`] of the compositor.
+pub fn fetch() -> Task<Capabilities> {
+    action::oneshot(action::Action::GetCapabilities)
+}
@@ -1,19 +1,185 @@
+use std::{ffi::c_void, mem};
+
+use sctk::reexports::client::{protocol::wl_display::WlDisplay, Proxy};
+
 use crate::core::clipboard::Kind;
 
-pub struct Clipboard {}
+/// The clipboard of the shell, backed by `smithay-clipboard`.
+///
+/// The data devices of the worker live in the event queue of its own thread.
+/// `smithay-clipboard` handles `wl_data_source::cancelled` there and does not
+/// expose it, so the standard selection is offered by the shell instead,
+/// with its own data source; see [`selection`](crate::selection). The
+/// worker only offers it when the shell cannot, and offers the primary
+/// selection.
+///
+/// Reading a selection is left to the worker, which negotiates the MIME type
+/// with the offer on its own. The preference order of the shell,
+/// [`dnd::TEXT`](crate::dnd::TEXT), down to the Latin-1 `STRING`, applies to
+/// the text dropped onto its surfaces, which it reads itself.
+pub struct Clipboard {
+    state: State,
+}
+
+enum State {
+    Connected {
+        clipboard: smithay_clipboard::Clipboard,
+        /// The standard selection while the shell offers it.
+        owned: Option<String>,
+        /// Whether the owned selection was written since it was last offered.
+        unoffered: bool,
+    },
+    #[cfg(any(test, feature = "memory-clipboard"))]
+    Memory {
+        standard: Option<String>,
+        primary: Option<String>,
+    },
+    Unavailable,
+}
 
 impl Clipboard {
+    pub fn connect(display: &WlDisplay) -> Self {
+        let display = display.id().as_ptr() as *mut c_void;
+
+        // SAFETY: the display outlives the clipboard, since the clipboard is
+        // disconnected when the shell tears down its surfaces on exit.
+        #[allow(unsafe_code)]
+        let clipboard = unsafe { smithay_clipboard::Clipboard::new(display) };
+
+        Self {
+            state: State::Connected {
+                clipboard,
+                owned: None,
+                unoffered: false,
+            },
+        }
+    }
+
+    /// Creates a [`Clipboard`] that keeps its contents in memory, without
+    /// touching the selections of the compositor.
+    #[cfg(any(test, feature = "memory-clipboard"))]
+    pub fn memory() -> Self {
+        Self {
+            state: State::Memory {
+                standard: None,
+                primary: None,
+            },
+        }
+    }
+
     pub fn unconnected() -> Self {
-        Self {}
+        Self {
+            state: State::Unavailable,
+        }
+    }
+
+    /// Takes the standard selection written since it was last offered, for
+    /// the shell to offer it.
+    pub fn take_unoffered(&mut self) -> Option<String> {
+        match &mut self.state {
+            State::Connected {
+                owned, unoffered, ..
+            } if mem::take(unoffered) => owned.clone(),
+            _ => None,
+        }
+    }
+
+    /// Returns the standard selection offered by the shell.
+    pub fn owned(&self) -> Option<&str> {
+        match &self.state {
+            State::Connected { owned, .. } => owned.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Hands the standard selection over to the worker, when the shell
+    /// cannot offer it itself.
+    pub fn hand_over(&mut self, contents: String) {
+        if let State::Connected {
+            clipboard, owned, ..
+        } = &mut self.state
+        {
+            *owned = None;
+            clipboard.store(contents);
+        }
+    }
+
+    /// Forgets the standard selection offered by the shell, once another
+    /// client takes it over.
+    pub fn disown(&mut self) {
+        if let State::Connected { owned, .. } = &mut self.state {
+            *owned = None;
+        }
     }
 }
 
 impl crate::core::Clipboard for Clipboard {
     fn read(&self, kind: Kind) -> Option<String> {
-        todo!("Clipboard::read")
+        match &self.state {
+            // The worker would wait for the shell to send the selection it
+            // owns, while the shell waits for the worker.
+            State::Connected {
+                owned: Some(owned), ..
+            } if matches!(kind, Kind::Standard) => Some(owned.clone()),
+            State::Connected { clipboard, .. } => match kind {
+                Kind::Standard => clipboard.load(),
+                Kind::Primary => clipboard.load_primary(),
+            }
+            .ok(),
+            #[cfg(any(test, feature = "memory-clipboard"))]
+            State::Memory { standard, primary } => match kind {
+                Kind::Standard => standard.clone(),
+                Kind::Primary => primary.clone(),
+            },
+            State::Unavailable => None,
+        }
     }
 
     fn write(&mut self, kind: Kind, contents: String) {
-        todo!("Clipboard::write")
+        match &mut self.state {
+            State::Connected {
+                clipboard,
+                owned,
+                unoffered,
+            } => match kind {
+                Kind::Standard => {
+                    *owned = Some(contents);
+                    *unoffered = true;
+                }
+                Kind::Primary => clipboard.store_primary(contents),
+            },
+            #[cfg(any(test, feature = "memory-clipboard"))]
+            State::Memory { standard, primary } => match kind {
+                Kind::Standard => *standard = Some(contents),
+                Kind::Primary => *primary = Some(contents),
+            },
+            State::Unavailable => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Clipboard as _;
+
+    #[test]
+    fn memory_clipboard_keeps_selections_apart() {
+        let mut clipboard = Clipboard::memory();
+        assert_eq!(clipboard.read(Kind::Standard), None);
+
+        clipboard.write(Kind::Standard, String::from("copied"));
+        clipboard.write(Kind::Primary, String::from("selected"));
+
+        assert_eq!(clipboard.read(Kind::Standard).as_deref(), Some("copied"));
+        assert_eq!(clipboard.read(Kind::Primary).as_deref(), Some("selected"));
+    }
+
+    #[test]
+    fn unconnected_clipboard_is_empty() {
+        let mut clipboard = Clipboard::unconnected();
+        clipboard.write(Kind::Standard, String::from("lost"));
+
+        assert_eq!(clipboard.read(Kind::Standard), None);
     }
 }
@@ -21,6 +21,65 @@ pub mod mouse {
         }
     }
 
+    /// Converts a pointer position in surface coordinates into a
+    /// `CursorMoved` event in the coordinates of the user interface.
+    pub fn cursor_moved(position: core::Point, scale_factor: f64) -> core::mouse::Event {
+        core::mouse::Event::CursorMoved {
+            position: logical(position, scale_factor),
+        }
+    }
+
+    /// Converts a position in surface coordinates into the coordinates of
+    /// the user interface.
+    pub fn logical(position: core::Point, scale_factor: f64) -> core::Point {
+        core::Point::new(
+            position.x / (scale_factor as f32),
+            position.y / (scale_factor as f32),
+        )
+    }
+
+    /// Returns the events of a pointer entering a surface at the given
+    /// position in surface coordinates.
+    ///
+    /// The position is reported right away, so widgets are hovered as soon
+    /// as the pointer enters instead of on its first motion.
+    pub fn entered(position: core::Point, scale_factor: f64) -> [core::mouse::Event; 2] {
+        [
+            core::mouse::Event::CursorEntered,
+            cursor_moved(position, scale_factor),
+        ]
+    }
+
+    /// Returns the events of a pointer scrolling at the given position in
+    /// surface coordinates.
+    ///
+    /// The position is reported again after scrolling, so the widget that
+    /// scrolled under a stationary cursor is hovered right away, like with
+    /// `winit`.
+    pub fn scrolled(
+        horizontal: f64,
+        vertical: f64,
+        position: core::Point,
+        scale_factor: f64,
+    ) -> [core::mouse::Event; 2] {
+        [
+            core::mouse::Event::WheelScrolled {
+                delta: scroll_delta(horizontal, vertical),
+            },
+            cursor_moved(position, scale_factor),
+        ]
+    }
+
+    /// Converts the absolute values of a pointer axis event into a
+    /// [`core::mouse::ScrollDelta`], keeping each axis independent so that
+    /// horizontal tilt wheels only produce horizontal scrolling.
+    pub fn scroll_delta(horizontal: f64, vertical: f64) -> core::mouse::ScrollDelta {
+        core::mouse::ScrollDelta::Pixels {
+            x: horizontal as f32,
+            y: vertical as f32,
+        }
+    }
+
     pub fn icon(interaction: Interaction) -> CursorIcon {
         match interaction {
             Interaction::None => CursorIcon::Default,
@@ -46,6 +105,102 @@ pub mod mouse {
     }
 }
 
+pub mod input_method {
+    use sctk::reexports::protocols::wp::text_input::zv3::client::zwp_text_input_v3::{
+        ContentHint, ContentPurpose,
+    };
+
+    use super::core;
+
+    pub fn content_type(purpose: core::input_method::Purpose) -> (ContentHint, ContentPurpose) {
+        use core::input_method::Purpose;
+
+        match purpose {
+            Purpose::Normal => (ContentHint::None, ContentPurpose::Normal),
+            Purpose::Secure => (
+                ContentHint::HiddenText | ContentHint::SensitiveData,
+                ContentPurpose::Password,
+            ),
+            Purpose::Terminal => (ContentHint::None, ContentPurpose::Terminal),
+        }
+    }
+}
+
+pub mod layer_shell {
+    use sctk::shell::wlr_layer;
+
+    use super::core;
+
+    pub fn layer(layer: core::layer_shell::Layer) -> wlr_layer::Layer {
+        use core::layer_shell::Layer;
+
+        match layer {
+            Layer::Background => wlr_layer::Layer::Background,
+            Layer::Bottom => wlr_layer::Layer::Bottom,
+            Layer::Top => wlr_layer::Layer::Top,
+            Layer::Overlay => wlr_layer::Layer::Overlay,
+        }
+    }
+
+    pub fn anchor(anchor: core::layer_shell::Anchor) -> wlr_layer::Anchor {
+        wlr_layer::Anchor::from_bits(anchor.bits()).unwrap()
+    }
+
+    /// Returns the exclusive zone that reserves the whole anchored dimension
+    /// of a surface of the given size.
+    ///
+    /// The compositor only honors an exclusive zone for surfaces anchored to
+    /// a single edge, optionally along with both perpendicular edges; other
+    /// surfaces get no exclusive zone.
+    pub fn auto_exclusive_zone(anchor: core::layer_shell::Anchor, size: core::Size<u32>) -> i32 {
+        use core::layer_shell::Anchor;
+
+        let vertical = anchor.contains(Anchor::TOP) != anchor.contains(Anchor::BOTTOM);
+        let horizontal = anchor.contains(Anchor::LEFT) != anchor.contains(Anchor::RIGHT);
+
+        let zone = match (vertical, horizontal) {
+            (true, false) => size.height,
+            (false, true) => size.width,
+            _ => 0,
+        };
+
+        i32::try_from(zone).unwrap_or(i32::MAX)
+    }
+
+    /// Returns the size of a surface from the size in its configure and the
+    /// requested one.
+    ///
+    /// The compositor chooses the dimensions that were requested as zero
+    /// (e.g. to cover the whole output), while a zero dimension in the
+    /// configure lets the surface keep the requested one.
+    pub fn configured_size(requested: core::Size<u32>, configured: (u32, u32)) -> core::Size<u32> {
+        let choose = |configured, requested| {
+            if configured == 0 {
+                requested
+            } else {
+                configured
+            }
+        };
+
+        core::Size::new(
+            choose(configured.0, requested.width),
+            choose(configured.1, requested.height),
+        )
+    }
+
+    pub fn keyboard_interactivity(
+        keyboard_interactivity: core::layer_shell::KeyboardInteractivity,
+    ) -> wlr_layer::KeyboardInteractivity {
+        use core::layer_shell::KeyboardInteractivity;
+
+        match keyboard_interactivity {
+            KeyboardInteractivity::None => wlr_layer::KeyboardInteractivity::None,
+            KeyboardInteractivity::Exclusive => wlr_layer::KeyboardInteractivity::Exclusive,
+            KeyboardInteractivity::OnDemand => wlr_layer::KeyboardInteractivity::OnDemand,
+        }
+    }
+}
+
 pub mod keyboard {
     use iced_debug::core::SmolStr;
 
@@ -70,6 +225,48 @@ pub mod keyboard {
         m
     }
 
+    /// Returns the modifiers that the given held keys activate.
+    pub fn held_modifiers(keysyms: &[sctk::seat::keyboard::Keysym]) -> core::keyboard::Modifiers {
+        use core::keyboard::Modifiers as M;
+
+        use sctk::seat::keyboard::Keysym as SK;
+        keysyms.iter().fold(M::empty(), |modifiers, keysym| {
+            modifiers
+                | match *keysym {
+                    SK::Shift_L | SK::Shift_R => M::SHIFT,
+                    SK::Control_L | SK::Control_R => M::CTRL,
+                    // Meta shares the modifier of Alt in the layouts of
+                    // xkeyboard-config, while Super is the logo key.
+                    SK::Alt_L | SK::Alt_R | SK::Meta_L | SK::Meta_R => M::ALT,
+                    SK::Super_L | SK::Super_R => M::LOGO,
+                    _ => M::empty(),
+                }
+        })
+    }
+
+    /// Returns the key presses of a keyboard entering a surface with the
+    /// given keys held, along with their raw codes.
+    ///
+    /// Only the modifier keys are pressed, so the program does not miss
+    /// modifiers that are already down. The other keys were pressed for
+    /// another surface (e.g. the `Enter` that launched the application from a
+    /// terminal, or a binding of the compositor), and a text input would act
+    /// on them.
+    pub fn entered(
+        keysyms: &[sctk::seat::keyboard::Keysym],
+        raw: &[u32],
+        modifiers: core::keyboard::Modifiers,
+    ) -> Vec<core::keyboard::Event> {
+        let modifiers = modifiers | held_modifiers(keysyms);
+
+        keysyms
+            .iter()
+            .zip(raw)
+            .filter(|(keysym, _)| keysym.is_modifier_key())
+            .map(|(&keysym, &raw_code)| pressed(keysym, raw_code, None, modifiers))
+            .collect()
+    }
+
     pub fn key(keysym: sctk::seat::keyboard::Keysym) -> core::keyboard::Key {
         use core::keyboard::{key::Named as N, Key as IK};
 
@@ -147,6 +344,24 @@ pub mod keyboard {
         })
     }
 
+    pub fn pressed(
+        keysym: sctk::seat::keyboard::Keysym,
+        raw_code: u32,
+        text: Option<SmolStr>,
+        modifiers: core::keyboard::Modifiers,
+    ) -> core::keyboard::Event {
+        let key = key(keysym);
+
+        core::keyboard::Event::KeyPressed {
+            key: key.clone(),
+            modified_key: key, // TODO: actually get modified key
+            physical_key: code(keysym, raw_code),
+            location: location(keysym),
+            modifiers,
+            text,
+        }
+    }
+
     pub fn location(keysym: sctk::seat::keyboard::Keysym) -> core::keyboard::Location {
         use core::keyboard::Location;
 
@@ -169,3 +384,146 @@ pub mod keyboard {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrolling_reports_the_stationary_cursor_again() {
+        let [scrolled, moved] = mouse::scrolled(0.0, 30.0, core::Point::new(40.0, 80.0), 2.0);
+
+        assert_eq!(
+            scrolled,
+            core::mouse::Event::WheelScrolled {
+                delta: core::mouse::ScrollDelta::Pixels { x: 0.0, y: 30.0 },
+            }
+        );
+        assert_eq!(
+            moved,
+            core::mouse::Event::CursorMoved {
+                position: core::Point::new(20.0, 40.0),
+            }
+        );
+    }
+
+    #[test]
+    fn entering_reports_the_logical_position() {
+        let events = mouse::entered(core::Point::new(300.0, 150.0), 1.5);
+
+        assert_eq!(
+            events,
+            [
+                core::mouse::Event::CursorEntered,
+                core::mouse::Event::CursorMoved {
+                    position: core::Point::new(200.0, 100.0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn auto_exclusive_zone_follows_the_anchored_edge() {
+        use core::layer_shell::Anchor;
+
+        let size = core::Size::new(1920, 32);
+        let zone = |anchor| layer_shell::auto_exclusive_zone(anchor, size);
+
+        assert_eq!(zone(Anchor::TOP), 32);
+        assert_eq!(zone(Anchor::TOP | Anchor::LEFT | Anchor::RIGHT), 32);
+        assert_eq!(zone(Anchor::LEFT | Anchor::TOP | Anchor::BOTTOM), 1920);
+        assert_eq!(zone(Anchor::TOP | Anchor::LEFT), 0);
+        assert_eq!(zone(Anchor::all()), 0);
+    }
+
+    #[test]
+    fn surface_covering_the_output_adopts_its_size() {
+        let settings = crate::layer_shell::covering(core::layer_shell::Layer::Overlay);
+
+        assert_eq!(
+            layer_shell::configured_size(settings.size, (2560, 1440)),
+            core::Size::new(2560, 1440)
+        );
+        assert_eq!(
+            layer_shell::configured_size(core::Size::new(400, 32), (0, 0)),
+            core::Size::new(400, 32)
+        );
+        assert_eq!(
+            layer_shell::configured_size(core::Size::new(0, 32), (1920, 0)),
+            core::Size::new(1920, 32)
+        );
+    }
+
+    #[test]
+    fn horizontal_tilt_scrolls_horizontally() {
+        assert_eq!(
+            mouse::scroll_delta(15.0, 0.0),
+            core::mouse::ScrollDelta::Pixels { x: 15.0, y: 0.0 }
+        );
+        assert_eq!(
+            mouse::scroll_delta(-15.0, 0.0),
+            core::mouse::ScrollDelta::Pixels { x: -15.0, y: 0.0 }
+        );
+    }
+
+    #[test]
+    fn key_without_keysym_keeps_its_raw_code() {
+        use core::keyboard::{
+            key::{NativeCode, Physical},
+            Event, Key, Modifiers,
+        };
+        use sctk::seat::keyboard::Keysym;
+
+        let Event::KeyPressed {
+            key, physical_key, ..
+        } = keyboard::pressed(Keysym::NoSymbol, 248, None, Modifiers::empty())
+        else {
+            panic!("expected a key press");
+        };
+
+        assert_eq!(key, Key::Unidentified);
+        assert_eq!(physical_key, Physical::Unidentified(NativeCode::Xkb(248)));
+    }
+
+    #[test]
+    fn entering_with_shift_held_presses_shift() {
+        use core::keyboard::{key::Named, Event, Key, Location, Modifiers};
+        use sctk::seat::keyboard::Keysym;
+
+        let Event::KeyPressed {
+            key,
+            location,
+            modifiers,
+            text,
+            ..
+        } = keyboard::pressed(Keysym::Shift_L, 42, None, Modifiers::SHIFT)
+        else {
+            panic!("expected a key press");
+        };
+
+        assert_eq!(key, Key::Named(Named::Shift));
+        assert_eq!(location, Location::Left);
+        assert_eq!(modifiers, Modifiers::SHIFT);
+        assert_eq!(text, None);
+    }
+
+    #[test]
+    fn entering_presses_only_the_held_modifiers() {
+        use core::keyboard::{key::Named, Event, Key, Modifiers};
+        use sctk::seat::keyboard::Keysym;
+
+        // Shift and Enter are held, as when launching from a terminal.
+        let events = keyboard::entered(
+            &[Keysym::Shift_L, Keysym::Return],
+            &[42, 28],
+            Modifiers::empty(),
+        );
+
+        let [Event::KeyPressed { key, modifiers, .. }] = events.as_slice() else {
+            panic!("expected a single key press, got {events:?}");
+        };
+
+        assert_eq!(*key, Key::Named(Named::Shift));
+        assert_eq!(*modifiers, Modifiers::SHIFT);
+    }
+}
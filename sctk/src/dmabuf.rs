@@ -0,0 +1,95 @@
+//! Find the buffer formats that a surface can be scanned out with.
+//!
+//! The compositor sends the feedback of `zwp_linux_dmabuf_v1` for every
+//! surface, and updates it when the surface moves to another output or
+//! becomes full-screen. A buffer in a format of a scanout tranche can be
+//! shown by the display controller directly, without the compositor copying
+//! it, which saves power for video and games.
+//!
+//! The renderer of iced picks the formats of its own surfaces through
+//! `wgpu`, which does not take modifiers, so it keeps its current format
+//! selection. This feedback is meant for content rendered by another
+//! pipeline, like a hardware video decoder drawing into a
+//! [`Subsurface`](crate::subsurface::Subsurface).
+use sctk::{
+    dmabuf::DmabufFeedback,
+    reexports::{
+        client::WEnum,
+        protocols::wp::linux_dmabuf::zv1::client::zwp_linux_dmabuf_feedback_v1::TrancheFlags,
+    },
+};
+
+use crate::{action, core::window::Id, runtime::Task};
+
+/// A buffer format, as a DRM fourcc code and a format modifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Format {
+    /// The DRM fourcc code of the format (e.g. `XR24`).
+    pub fourcc: u32,
+
+    /// The DRM format modifier, which describes the layout of the buffer in
+    /// memory.
+    pub modifier: u64,
+}
+
+/// The dmabuf feedback of a surface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Feedback {
+    /// The device number of the main device of the compositor, which
+    /// buffers should be allocated on by default.
+    pub main_device: u64,
+
+    /// The formats that the surface can be scanned out with, from the most
+    /// to the least preferred.
+    pub scanout: Vec<Format>,
+
+    /// Every format supported for the surface, from the most to the least
+    /// preferred.
+    pub formats: Vec<Format>,
+}
+
+impl From<&DmabufFeedback> for Feedback {
+    fn from(feedback: &DmabufFeedback) -> Self {
+        let table = feedback.format_table();
+
+        let formats_of = |indices: &[u16]| {
+            indices
+                .iter()
+                .filter_map(|index| table.get(usize::from(*index)))
+                .map(|format| Format {
+                    fourcc: format.format,
+                    modifier: format.modifier,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut scanout = Vec::new();
+        let mut formats = Vec::new();
+
+        for tranche in feedback.tranches() {
+            let tranche_formats = formats_of(&tranche.formats);
+
+            if matches!(tranche.flags, WEnum::Value(flags) if flags.contains(TrancheFlags::Scanout))
+            {
+                scanout.extend_from_slice(&tranche_formats);
+            }
+
+            formats.extend(tranche_formats);
+        }
+
+        Self {
+            main_device: feedback.main_device(),
+            scanout,
+            formats,
+        }
+    }
+}
+
+/// Listens to the dmabuf [`Feedback`] of the surface with the given [`Id`].
+///
+/// The current feedback is produced first, followed by every update. The
+/// [`Task`] finishes right away if the compositor does not support per-surface
+/// feedback (version 4 of `zwp_linux_dmabuf_v1`).
+pub fn feedback(id: Id) -> Task<Feedback> {
+    action::stream(move |channel| action::Action::ListenDmabufFeedback(id, channel))
+}
@@ -0,0 +1,196 @@
+//! Handle the data of drag and drop operations.
+//!
+//! The shell accepts the files dragged onto its surfaces, offered as a
+//! [`URI_LIST`] by file managers. Each dropped file produces a
+//! [`FileDropped`](crate::core::window::Event::FileDropped) event for the
+//! window, and the whole drop can be listened to at once with
+//! [`listen_files`].
+//!
+//! Text is accepted too, read with the MIME type chosen by
+//! [`preferred_text`], and pasted into the focusable widget under the drop.
+use std::path::PathBuf;
+
+use crate::{
+    action,
+    core::{window, Point},
+    runtime::Task,
+};
+
+/// The MIME type of a list of URIs, offered when dragging files.
+pub const URI_LIST: &str = "text/uri-list";
+
+/// The MIME types of plain text, from the most to the least preferred.
+///
+/// GTK and Qt offer `text/plain;charset=utf-8`, while X11 programs running
+/// through Xwayland and some terminals only offer the legacy `UTF8_STRING`
+/// and `STRING` atoms. `STRING` is Latin-1, so it is the last resort.
+pub const TEXT: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "TEXT",
+    "STRING",
+];
+
+/// Files that were dropped onto a surface.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilesDropped {
+    /// The [`window::Id`] of the surface the files were dropped onto.
+    pub window: window::Id,
+
+    /// The position of the drop, in logical coordinates.
+    pub position: Point,
+
+    /// The paths of the dropped files.
+    pub paths: Vec<PathBuf>,
+
+    /// Every MIME type offered by the source of the drag, like the types of
+    /// the files that some file managers offer along with the [`URI_LIST`].
+    pub mime_types: Vec<String>,
+}
+
+/// Listens to the [`FilesDropped`] onto every surface of the application.
+pub fn listen_files() -> Task<FilesDropped> {
+    action::stream(action::Action::ListenDroppedFiles)
+}
+
+/// Chooses the MIME type to read plain text with among the ones advertised
+/// by an offer, following the preference order of [`TEXT`].
+///
+/// The charset parameter is compared without regard to case or spacing
+/// (e.g. `text/plain; charset=UTF-8`); the MIME type offered by the source is
+/// returned as is, since it must be requested verbatim.
+pub fn preferred_text<'a>(offered: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    offered
+        .into_iter()
+        .filter_map(|mime_type| {
+            let normalized: String = mime_type.chars().filter(|c| !c.is_whitespace()).collect();
+
+            let rank = TEXT
+                .iter()
+                .position(|text| text.eq_ignore_ascii_case(&normalized))?;
+
+            Some((rank, mime_type))
+        })
+        .min_by_key(|(rank, _)| *rank)
+        .map(|(_, mime_type)| mime_type)
+}
+
+/// Decodes text read with one of the [`TEXT`] MIME types.
+///
+/// `STRING` is Latin-1, and every other type is UTF-8.
+pub fn decode_text(mime_type: &str, bytes: &[u8]) -> String {
+    if mime_type == "STRING" {
+        bytes.iter().copied().map(char::from).collect()
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+/// Parses a [`URI_LIST`] into the paths of the local files it contains.
+///
+/// Comments and URIs that do not point to local files (e.g. `https://`) are
+/// skipped, and percent-encoded characters are decoded.
+pub fn parse_uri_list(list: &str) -> Vec<PathBuf> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|uri| {
+            let path = uri.strip_prefix("file://")?;
+
+            // The host is either empty or `localhost` for local files.
+            let path = path.strip_prefix("localhost").unwrap_or(path);
+
+            if !path.starts_with('/') {
+                return None;
+            }
+
+            percent_decode(path).map(PathBuf::from)
+        })
+        .collect()
+}
+
+fn percent_decode(encoded: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut input = encoded.bytes();
+
+    while let Some(byte) = input.next() {
+        if byte == b'%' {
+            let high = char::from(input.next()?).to_digit(16)?;
+            let low = char::from(input.next()?).to_digit(16)?;
+
+            bytes.push((high * 16 + low) as u8);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_local_files() {
+        let list = "# dragged from a file manager\r\n\
+            file:///home/user/Music/song.flac\r\n\
+            file://localhost/home/user/My%20Album/track%2001.ogg\r\n\
+            https://example.com/remote.mp3\r\n";
+
+        assert_eq!(
+            parse_uri_list(list),
+            vec![
+                PathBuf::from("/home/user/Music/song.flac"),
+                PathBuf::from("/home/user/My Album/track 01.ogg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn chooses_the_best_offered_text() {
+        let matrix: [(&[&str], Option<&str>); 8] = [
+            // GTK
+            (
+                &["text/plain", "text/plain;charset=utf-8", "UTF8_STRING"],
+                Some("text/plain;charset=utf-8"),
+            ),
+            // Qt
+            (
+                &["text/html", "text/plain; charset=UTF-8", "text/plain"],
+                Some("text/plain; charset=UTF-8"),
+            ),
+            // Xwayland
+            (
+                &["TARGETS", "STRING", "UTF8_STRING", "TEXT"],
+                Some("UTF8_STRING"),
+            ),
+            (&["image/png", "text/plain"], Some("text/plain")),
+            (&["TEXT", "STRING"], Some("TEXT")),
+            (&["STRING"], Some("STRING")),
+            (&["text/plain;charset=iso-8859-1"], None),
+            (&["image/png", URI_LIST], None),
+        ];
+
+        for (offered, expected) in matrix {
+            assert_eq!(
+                preferred_text(offered.iter().copied()),
+                expected,
+                "{offered:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_latin1_strings() {
+        assert_eq!(decode_text("STRING", b"caf\xe9"), "café");
+        assert_eq!(decode_text("UTF8_STRING", "café".as_bytes()), "café");
+    }
+
+    #[test]
+    fn skips_malformed_uris() {
+        assert!(parse_uri_list("file:///broken%2").is_empty());
+        assert!(parse_uri_list("file://remote-host/file").is_empty());
+    }
+}
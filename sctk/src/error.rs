@@ -1,3 +1,5 @@
+pub use sctk::reexports::client::protocol::wl_shm::Format as ShmFormat;
+
 use crate::{futures::futures, graphics};
 
 /// An error that occurred while running an application.
@@ -10,6 +12,25 @@ pub enum Error {
     /// The application graphics context could not be created.
     #[error("the application graphics context could not be created")]
     GraphicsCreationFailed(graphics::Error),
+
+    /// The software renderer needs a shared memory format the compositor
+    /// does not support.
+    #[error("the compositor does not support the {0:?} shared memory format")]
+    UnsupportedShmFormat(ShmFormat),
+
+    /// The compositor does not support layer surfaces
+    /// (`zwlr_layer_shell_v1`), which every surface of the shell is opened
+    /// as, like on GNOME.
+    #[error("the compositor does not support layer surfaces (zwlr_layer_shell_v1)")]
+    LayerShellUnsupported,
+
+    /// The connection to the compositor was lost, like when it crashed or
+    /// was restarted.
+    ///
+    /// Every surface is gone at that point, so the application may only be
+    /// restarted from scratch.
+    #[error("the connection to the compositor was lost")]
+    ConnectionLost,
 }
 
 impl From<graphics::Error> for Error {
@@ -0,0 +1,51 @@
+//! Process the events of a window before they reach its user interface.
+use std::mem;
+
+use crate::core::{mouse, Event};
+
+/// Keeps only the latest of every run of consecutive `CursorMoved` events.
+///
+/// The pointer may move many times between two frames, and every position
+/// but the last one is stale by the time the user interface is updated. The
+/// events in between (e.g. button presses) are kept in order, so a press is
+/// still reported at the position the cursor had when it happened.
+pub fn coalesce_cursor_moves(events: &mut Vec<Event>) {
+    events.dedup_by(|next, previous| {
+        let is_cursor_moved =
+            |event: &Event| matches!(event, Event::Mouse(mouse::Event::CursorMoved { .. }));
+
+        if is_cursor_moved(next) && is_cursor_moved(previous) {
+            mem::swap(next, previous);
+            true
+        } else {
+            false
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Point;
+
+    fn moved(x: f32) -> Event {
+        Event::Mouse(mouse::Event::CursorMoved {
+            position: Point::new(x, 0.0),
+        })
+    }
+
+    #[test]
+    fn motions_between_frames_coalesce_around_presses() {
+        let pressed = Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left));
+        let released = Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left));
+
+        let mut events: Vec<_> = (0..1000).map(|x| moved(x as f32)).collect();
+        events.push(pressed.clone());
+        events.extend((1000..2000).map(|x| moved(x as f32)));
+        events.push(released.clone());
+
+        coalesce_cursor_moves(&mut events);
+
+        assert_eq!(events, [moved(999.0), pressed, moved(1999.0), released]);
+    }
+}
@@ -0,0 +1,48 @@
+//! Query the fonts that are available to the renderer.
+//!
+//! The system fonts and every font loaded by the application share a single
+//! font system. A family that is not found there is silently replaced by a
+//! fallback, so applications that let users pick a font can check for it
+//! first.
+//!
+//! Fonts cannot be unloaded: the paragraphs that were shaped with a font
+//! keep referring to it, and the renderers cache its glyphs.
+use crate::{action, graphics::text, runtime::Task};
+
+/// Fetches the names of every font family that is available, sorted and
+/// without duplicates.
+///
+/// Fonts loaded before the first surface is opened are only listed once it
+/// is, since they are loaded along with the renderer.
+pub fn families() -> Task<Vec<String>> {
+    action::oneshot(action::Action::ListFontFamilies)
+}
+
+/// Fetches whether a font family with the given name is available, ignoring
+/// case.
+pub fn is_available(family: impl Into<String>) -> Task<bool> {
+    let family = family.into();
+
+    families().map(move |families| {
+        families
+            .iter()
+            .any(|available| available.eq_ignore_ascii_case(&family))
+    })
+}
+
+/// Returns the font families of the font system.
+pub(crate) fn loaded_families() -> Vec<String> {
+    let mut font_system = text::font_system().write().expect("Write font system");
+
+    let mut families: Vec<String> = font_system
+        .raw()
+        .db()
+        .faces()
+        .flat_map(|face| face.families.iter().map(|(family, _)| family.clone()))
+        .collect();
+
+    families.sort_unstable();
+    families.dedup();
+
+    families
+}
@@ -0,0 +1,102 @@
+//! Tell input methods about the text being edited.
+//!
+//! The input method of a widget only knows where its caret is. Predictive
+//! input and reconversion, which are common in CJK input methods, also need
+//! the text around the caret, which only the application can provide since
+//! widgets do not expose their contents.
+use crate::{action, core::window, runtime::Task};
+
+/// The maximum length of the text that the protocol accepts, in bytes.
+const MAX_LEN: usize = 4000;
+
+/// The text around the caret of the focused text widget.
+///
+/// The offsets are in bytes and must fall on character boundaries. The text
+/// must not include the preedit that is being composed: the input method
+/// inserts it at the cursor on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SurroundingText {
+    /// The text of the widget, or a paragraph of it around the caret.
+    pub text: String,
+    /// The offset of the caret.
+    pub cursor: usize,
+    /// The offset of the other end of the selection, which is the same as
+    /// the `cursor` if nothing is selected.
+    pub anchor: usize,
+}
+
+impl SurroundingText {
+    /// Returns the part of the text that fits in the protocol, centered on
+    /// the selection, along with the cursor and anchor offsets within it.
+    pub(crate) fn truncated(&self) -> (&str, i32, i32) {
+        let len = self.text.len();
+        let cursor = self.cursor.min(len);
+        let anchor = self.anchor.min(len);
+
+        let (start, end) = if len <= MAX_LEN {
+            (0, len)
+        } else {
+            let middle = (cursor.min(anchor) + cursor.max(anchor)) / 2;
+            let end = (middle.saturating_sub(MAX_LEN / 2) + MAX_LEN).min(len);
+            let mut start = end - MAX_LEN;
+            let mut end = end;
+
+            while !self.text.is_char_boundary(start) {
+                start += 1;
+            }
+
+            while !self.text.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            (start, end)
+        };
+
+        let offset = |offset: usize| (offset.clamp(start, end) - start) as i32;
+
+        (&self.text[start..end], offset(cursor), offset(anchor))
+    }
+}
+
+/// Sets the [`SurroundingText`] of the focused text widget of the window
+/// with the given [`window::Id`], or clears it.
+///
+/// It should be set again whenever the text or the caret of the widget
+/// changes, including after the edits of the input method itself. It is
+/// cleared along with the input method when no widget requests it anymore.
+pub fn set_surrounding_text<T>(id: window::Id, surrounding_text: Option<SurroundingText>) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetSurroundingText(id, surrounding_text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_text_is_centered_on_the_caret() {
+        let short = SurroundingText {
+            text: String::from("こんにちは"),
+            cursor: 6,
+            anchor: 3,
+        };
+        assert_eq!(short.truncated(), ("こんにちは", 6, 3));
+
+        // Every character is 3 bytes long, so the window does not fall on
+        // character boundaries at first.
+        let text = "あ".repeat(3000);
+        let long = SurroundingText {
+            cursor: 4500,
+            anchor: 4500,
+            text,
+        };
+        let (text, cursor, anchor) = long.truncated();
+
+        assert!(text.len() <= MAX_LEN);
+        assert_eq!(cursor, anchor);
+        assert!(text.is_char_boundary(cursor as usize));
+        assert!((cursor as usize).abs_diff(text.len() / 2) <= 3);
+    }
+}
@@ -0,0 +1,392 @@
+//! Track the keyboard focus and order keyboard events consistently.
+use std::hash::Hash;
+
+use rustc_hash::FxHashMap;
+use sctk::seat::keyboard::Keysym;
+
+use crate::core::{
+    keyboard::{self, key::Named, Key, Modifiers},
+    window, Event,
+};
+
+/// The windows focused by every keyboard.
+///
+/// A window may be focused by the keyboards of multiple seats at once; it is
+/// only unfocused once all of them have left it.
+///
+/// Focus is tracked by the surface the compositor enters, through the aliases
+/// of the window manager, so any surface registered there can be focused.
+#[derive(Debug)]
+pub struct Focuses<K> {
+    entries: FxHashMap<K, window::Id>,
+}
+
+impl<K> Default for Focuses<K> {
+    fn default() -> Self {
+        Self {
+            entries: FxHashMap::default(),
+        }
+    }
+}
+
+impl<K: Hash + Eq> Focuses<K> {
+    /// Returns the window focused by the keyboard, if any.
+    pub fn get(&self, keyboard: &K) -> Option<window::Id> {
+        self.entries.get(keyboard).copied()
+    }
+
+    /// Returns the windows focused by any keyboard.
+    pub fn windows(&self) -> impl Iterator<Item = window::Id> + '_ {
+        self.entries.values().copied()
+    }
+
+    /// Returns whether any keyboard focuses the window.
+    pub fn is_focused(&self, id: window::Id) -> bool {
+        self.entries.values().any(|focus| *focus == id)
+    }
+
+    /// Focuses the window with the keyboard.
+    ///
+    /// Returns the window that lost its last focus, if the keyboard moved
+    /// without leaving its previous window first, and whether the window
+    /// gained focus. A compositor re-sending `enter` does not focus a window
+    /// twice.
+    pub fn enter(&mut self, keyboard: K, id: window::Id) -> (Option<window::Id>, bool) {
+        let gained = !self.is_focused(id);
+        let previous = self.entries.insert(keyboard, id);
+
+        let lost = previous.filter(|previous| *previous != id && !self.is_focused(*previous));
+
+        (lost, gained)
+    }
+
+    /// Removes the focus of the keyboard, returning the window that lost its
+    /// last focus, if any.
+    pub fn leave(&mut self, keyboard: &K) -> Option<window::Id> {
+        let id = self.entries.remove(keyboard)?;
+
+        (!self.is_focused(id)).then_some(id)
+    }
+
+    /// Removes the focus of every keyboard on the window, returning whether
+    /// it was focused.
+    pub fn remove_window(&mut self, id: window::Id) -> bool {
+        let focuses = self.entries.len();
+        self.entries.retain(|_, focus| *focus != id);
+
+        self.entries.len() != focuses
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Returns the events of a window that lost its last keyboard focus.
+///
+/// Its modifiers are reset, since the keys released after the keyboard left
+/// are never reported to it. Any other window keeps its own modifiers.
+pub fn unfocused(id: window::Id) -> [(window::Id, Event); 2] {
+    [
+        (
+            id,
+            Event::Keyboard(keyboard::Event::ModifiersChanged(Modifiers::default())),
+        ),
+        (id, Event::Window(window::Event::Unfocused)),
+    ]
+}
+
+/// The AltGr keys held down on every keyboard.
+///
+/// AltGr selects the third shift level of the layout (e.g. `€` on `AltGr+E`).
+/// Neither the modifiers of iced nor the ones reported by `sctk` can express
+/// it, so it is derived from the keys that are held instead. They are tracked
+/// by raw code, since their keysym may change while they are held.
+#[derive(Debug)]
+pub struct AltGr<K> {
+    held: FxHashMap<K, Vec<u32>>,
+}
+
+impl<K> Default for AltGr<K> {
+    fn default() -> Self {
+        Self {
+            held: FxHashMap::default(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> AltGr<K> {
+    /// Tracks a key pressed on the keyboard, if it is an AltGr key.
+    pub fn press(&mut self, keyboard: &K, keysym: Keysym, raw_code: u32) {
+        if matches!(
+            keysym,
+            Keysym::ISO_Level3_Shift | Keysym::ISO_Level3_Latch | Keysym::Mode_switch
+        ) {
+            self.held
+                .entry(keyboard.clone())
+                .or_default()
+                .push(raw_code);
+        }
+    }
+
+    /// Forgets a key released on the keyboard.
+    pub fn release(&mut self, keyboard: &K, raw_code: u32) {
+        if let Some(held) = self.held.get_mut(keyboard) {
+            held.retain(|held| *held != raw_code);
+
+            if held.is_empty() {
+                let _ = self.held.remove(keyboard);
+            }
+        }
+    }
+
+    /// Returns whether an AltGr key is held on the keyboard.
+    pub fn is_held(&self, keyboard: &K) -> bool {
+        self.held.contains_key(keyboard)
+    }
+
+    /// Forgets the keys held on the keyboard, like when it leaves a window.
+    pub fn remove(&mut self, keyboard: &K) {
+        let _ = self.held.remove(keyboard);
+    }
+}
+
+/// Delays the key events of modifier keys until the modifiers they change
+/// have been reported.
+///
+/// Wayland sends the `modifiers` event after the `key` event of a modifier
+/// key, which would otherwise make a program observe a `KeyPressed` for
+/// Super while [`Modifiers::LOGO`] is still unset.
+#[derive(Debug, Default)]
+pub struct ModifierOrder {
+    pending: Option<(window::Id, keyboard::Event)>,
+}
+
+impl ModifierOrder {
+    /// Pushes a key event of the given window, delaying it if it belongs to
+    /// a modifier key.
+    pub fn key(
+        &mut self,
+        id: window::Id,
+        event: keyboard::Event,
+        events: &mut Vec<(window::Id, Event)>,
+    ) {
+        self.flush(events);
+
+        if is_modifier(&event) {
+            self.pending = Some((id, event));
+        } else {
+            events.push((id, Event::Keyboard(event)));
+        }
+    }
+
+    /// Pushes a change of modifiers of the given window, followed by the
+    /// delayed key event that caused it, if any.
+    pub fn modifiers(
+        &mut self,
+        id: window::Id,
+        modifiers: Modifiers,
+        events: &mut Vec<(window::Id, Event)>,
+    ) {
+        events.push((
+            id,
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)),
+        ));
+
+        if let Some((pending_id, mut event)) = self.pending.take() {
+            if let keyboard::Event::KeyPressed { modifiers: m, .. }
+            | keyboard::Event::KeyReleased { modifiers: m, .. } = &mut event
+                && pending_id == id
+            {
+                *m = modifiers;
+            }
+
+            events.push((pending_id, Event::Keyboard(event)));
+        }
+    }
+
+    /// Pushes the delayed key event, if any, as is.
+    ///
+    /// Not every modifier key changes the modifiers (e.g. when it is not
+    /// mapped in the keymap), so the delayed event must not be held forever.
+    pub fn flush(&mut self, events: &mut Vec<(window::Id, Event)>) {
+        if let Some((id, event)) = self.pending.take() {
+            events.push((id, Event::Keyboard(event)));
+        }
+    }
+}
+
+fn is_modifier(event: &keyboard::Event) -> bool {
+    let (keyboard::Event::KeyPressed { key, .. } | keyboard::Event::KeyReleased { key, .. }) =
+        event
+    else {
+        return false;
+    };
+
+    matches!(
+        key,
+        Key::Named(
+            Named::Shift | Named::Control | Named::Alt | Named::Super | Named::Meta | Named::Hyper
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alt_gr_is_held_until_every_alt_gr_key_is_released() {
+        let mut alt_gr = AltGr::default();
+
+        alt_gr.press(&1, Keysym::a, 38);
+        assert!(!alt_gr.is_held(&1));
+
+        alt_gr.press(&1, Keysym::ISO_Level3_Shift, 108);
+        alt_gr.press(&1, Keysym::Mode_switch, 92);
+        assert!(alt_gr.is_held(&1));
+        assert!(!alt_gr.is_held(&2));
+
+        alt_gr.release(&1, 108);
+        assert!(alt_gr.is_held(&1));
+
+        alt_gr.release(&1, 92);
+        assert!(!alt_gr.is_held(&1));
+    }
+
+    #[test]
+    fn repeated_enter_focuses_once() {
+        let id = window::Id::unique();
+        let mut focuses = Focuses::default();
+
+        assert_eq!(focuses.enter(1, id), (None, true));
+        assert_eq!(focuses.enter(1, id), (None, false));
+        assert_eq!(focuses.leave(&1), Some(id));
+        assert_eq!(focuses.leave(&1), None);
+    }
+
+    #[test]
+    fn focus_moving_with_shift_held_keeps_shift() {
+        use crate::conversion;
+
+        let first = window::Id::unique();
+        let second = window::Id::unique();
+        let mut focuses = Focuses::default();
+        let mut order = ModifierOrder::default();
+        let mut events = Vec::new();
+
+        let _ = focuses.enter(1, first);
+        order.modifiers(first, Modifiers::SHIFT, &mut events);
+
+        // The keyboard moves to the second window with Shift still held.
+        events.extend(focuses.leave(&1).into_iter().flat_map(unfocused));
+        assert_eq!(focuses.enter(1, second), (None, true));
+        events.extend(
+            conversion::keyboard::entered(&[Keysym::Shift_L], &[42], Modifiers::empty())
+                .into_iter()
+                .map(|event| (second, Event::Keyboard(event))),
+        );
+        order.modifiers(second, Modifiers::SHIFT, &mut events);
+
+        let modifiers_of = |id| {
+            events
+                .iter()
+                .filter(|(window, _)| *window == id)
+                .filter_map(|(_, event)| match event {
+                    Event::Keyboard(
+                        keyboard::Event::ModifiersChanged(modifiers)
+                        | keyboard::Event::KeyPressed { modifiers, .. },
+                    ) => Some(*modifiers),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Only the window that was left is reset.
+        assert_eq!(modifiers_of(first), [Modifiers::SHIFT, Modifiers::empty()]);
+        assert_eq!(modifiers_of(second), [Modifiers::SHIFT, Modifiers::SHIFT]);
+    }
+
+    #[test]
+    fn keyboard_removed_while_focused_unfocuses() {
+        let id = window::Id::unique();
+        let mut focuses = Focuses::default();
+
+        // A second seat gains a keyboard while the window is focused.
+        assert_eq!(focuses.enter(1, id), (None, true));
+        assert_eq!(focuses.enter(2, id), (None, false));
+
+        // The window stays focused until the last keyboard is removed.
+        assert_eq!(focuses.leave(&1), None);
+        assert!(focuses.is_focused(id));
+        assert_eq!(focuses.leave(&2), Some(id));
+        assert!(!focuses.is_focused(id));
+    }
+
+    #[test]
+    fn enter_without_leave_moves_focus() {
+        let first = window::Id::unique();
+        let second = window::Id::unique();
+        let mut focuses = Focuses::default();
+
+        let _ = focuses.enter(1, first);
+
+        assert_eq!(focuses.enter(1, second), (Some(first), true));
+        assert_eq!(focuses.get(&1), Some(second));
+    }
+
+    fn super_key(pressed: bool, modifiers: Modifiers) -> keyboard::Event {
+        let key = Key::Named(Named::Super);
+        let physical_key =
+            keyboard::key::Physical::Unidentified(keyboard::key::NativeCode::Xkb(125));
+
+        if pressed {
+            keyboard::Event::KeyPressed {
+                key: key.clone(),
+                modified_key: key,
+                physical_key,
+                location: keyboard::Location::Left,
+                modifiers,
+                text: None,
+            }
+        } else {
+            keyboard::Event::KeyReleased {
+                key: key.clone(),
+                modified_key: key,
+                physical_key,
+                location: keyboard::Location::Left,
+                modifiers,
+            }
+        }
+    }
+
+    #[test]
+    fn tapping_super_changes_modifiers_first() {
+        let id = window::Id::unique();
+        let mut order = ModifierOrder::default();
+        let mut events = Vec::new();
+
+        order.key(id, super_key(true, Modifiers::empty()), &mut events);
+        assert!(events.is_empty());
+
+        order.modifiers(id, Modifiers::LOGO, &mut events);
+        order.key(id, super_key(false, Modifiers::LOGO), &mut events);
+        order.modifiers(id, Modifiers::empty(), &mut events);
+
+        assert_eq!(
+            events,
+            vec![
+                (
+                    id,
+                    Event::Keyboard(keyboard::Event::ModifiersChanged(Modifiers::LOGO))
+                ),
+                (id, Event::Keyboard(super_key(true, Modifiers::LOGO))),
+                (
+                    id,
+                    Event::Keyboard(keyboard::Event::ModifiersChanged(Modifiers::empty()))
+                ),
+                (id, Event::Keyboard(super_key(false, Modifiers::empty()))),
+            ]
+        );
+    }
+}
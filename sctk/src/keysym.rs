@@ -0,0 +1,63 @@
+//! Listen to the raw keysyms of the keyboard and query its modifiers.
+//!
+//! The keyboard events of the user interface carry a [`Key`], which only
+//! names the keys that widgets care about. Terminal emulators and remapping
+//! tools need the keysym resolved by xkb instead, which cannot be recovered
+//! from a [`Key`] for every layout.
+//!
+//! [`Key`]: crate::core::keyboard::Key
+use crate::{
+    action,
+    core::{keyboard::Modifiers, window},
+    runtime::Task,
+};
+
+/// A key of the keyboard that was pressed or released.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// The [`window::Id`] of the surface with the keyboard focus.
+    pub window: window::Id,
+
+    /// The keysym resolved by xkb with the current layout and modifiers, as
+    /// defined in `xkbcommon-keysyms.h`.
+    pub keysym: u32,
+
+    /// The raw code of the key, as sent by the compositor.
+    pub raw_code: u32,
+
+    /// Whether the key was pressed; otherwise, it was released.
+    pub is_pressed: bool,
+
+    /// The text produced by the key press, if any.
+    pub text: Option<String>,
+
+    /// The modifiers of the keyboard, as reported to the user interface.
+    pub modifiers: Modifiers,
+
+    /// Whether AltGr (`ISO_Level3_Shift`) is held, which the [`Modifiers`]
+    /// cannot express.
+    ///
+    /// Shortcuts bound to AltGr should check this, since some layouts report
+    /// it as [`Modifiers::ALT`] and others as no modifier at all.
+    pub alt_gr: bool,
+}
+
+/// Listens to the keysyms of every key that is pressed or released while a
+/// surface of the application has the keyboard focus.
+///
+/// The keyboard events of the user interface are produced as usual, so this
+/// can be used alongside widgets.
+pub fn listen() -> Task<Event> {
+    action::stream(action::Action::ListenKeysyms)
+}
+
+/// Fetches the [`Modifiers`] that are currently held on the keyboards that
+/// focus a surface of the application.
+///
+/// This saves mirroring every `ModifiersChanged` event when the modifiers
+/// only matter at a given moment, like a drag that moves instead of copying
+/// while Shift is held. The modifiers are empty if no surface has the
+/// keyboard focus, since compositors only send them to a focused surface.
+pub fn modifiers() -> Task<Modifiers> {
+    action::oneshot(action::Action::GetModifiers)
+}
@@ -0,0 +1,267 @@
+//! Configure the layer surfaces of your application.
+//!
+//! Compositors match layer surfaces in their rules (e.g. to blur a bar or to
+//! exclude a launcher from screenshots) by their namespace, which acts as
+//! their tag. It can only be set when the surface is opened, through
+//! [`Settings::namespace`], and falls back to the application identifier of
+//! the shell settings. No protocol allows tagging a layer surface further.
+//!
+//! Placement is always decided by the compositor. A layer surface is shown on
+//! every workspace of its output, so the only hint it takes is the output in
+//! [`Settings::output`]; when it is unset, the compositor picks one.
+pub use crate::core::layer_shell::{Anchor, KeyboardInteractivity, Layer, Settings};
+use crate::{
+    action,
+    core::{window::Id, Padding, Size},
+    runtime::Task,
+};
+
+/// Creates the [`Settings`] of a layer surface of the given size, anchored
+/// to the given edges of its output.
+///
+/// The surface is placed on the [`Layer::Top`] layer, on the output chosen by
+/// the compositor, without margins or an exclusive zone, and takes the
+/// keyboard focus when clicked. Any of these can be changed with the struct
+/// update syntax:
+///
+/// ```ignore
+/// let bar = Settings {
+///     exclusive_zone: 32,
+///     ..layer_shell::anchored(
+///         Anchor::TOP | Anchor::LEFT | Anchor::RIGHT,
+///         Size::new(0, 32),
+///     )
+/// };
+/// ```
+///
+/// A zero dimension of the size is stretched between the anchored edges.
+pub fn anchored(anchor: Anchor, size: Size<u32>) -> Settings {
+    Settings {
+        layer: Layer::Top,
+        namespace: None,
+        size,
+        anchor,
+        exclusive_zone: 0,
+        margin: Padding {
+            top: 0,
+            right: 0,
+            bottom: 0,
+            left: 0,
+        },
+        keyboard_interactivity: KeyboardInteractivity::OnDemand,
+        output: None,
+    }
+}
+
+/// Creates the [`Settings`] of a layer surface that covers its whole output
+/// on the given [`Layer`], like a dimming overlay, a lock screen or a
+/// wallpaper.
+///
+/// The surface is anchored to every edge with a zero size, so the compositor
+/// sizes it to the output, and it ignores the exclusive zones of other
+/// surfaces, so bars do not shrink it. It is resized along with its output.
+pub fn covering(layer: Layer) -> Settings {
+    Settings {
+        layer,
+        exclusive_zone: -1,
+        ..anchored(Anchor::all(), Size::new(0, 0))
+    }
+}
+
+/// Changes the [`KeyboardInteractivity`] of the layer surface with the given
+/// [`Id`].
+///
+/// A launcher can request [`KeyboardInteractivity::Exclusive`] when it opens
+/// and release it with [`KeyboardInteractivity::None`] when it is dismissed.
+pub fn set_keyboard_interactivity<T>(
+    id: Id,
+    keyboard_interactivity: KeyboardInteractivity,
+) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetKeyboardInteractivity(
+        id,
+        keyboard_interactivity,
+    ))
+}
+
+/// The space reserved by a layer surface along the edge it is anchored to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExclusiveZone {
+    /// A fixed exclusive zone, like [`Settings::exclusive_zone`].
+    Fixed(i32),
+
+    /// An exclusive zone that follows the size of the surface: its height
+    /// when anchored to the top or bottom edge, and its width when anchored
+    /// to the left or right edge.
+    ///
+    /// This keeps the reserved space in sync with a bar whose size depends
+    /// on its contents.
+    Auto,
+}
+
+/// Changes the [`ExclusiveZone`] of the layer surface with the given [`Id`].
+///
+/// An [`ExclusiveZone::Auto`] zone is updated every time the surface is
+/// resized, until another [`ExclusiveZone`] is set.
+pub fn set_exclusive_zone<T>(id: Id, exclusive_zone: ExclusiveZone) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetExclusiveZone(id, exclusive_zone))
+}
+
+/// A set of changes to the state of a layer surface, applied together.
+///
+/// Every field left unset is kept as is.
+#[derive(Debug, Clone, Default)]
+pub struct Changes {
+    /// The new anchor of the surface.
+    pub anchor: Option<Anchor>,
+
+    /// The new size of the surface, where a zero dimension is stretched
+    /// between the anchored edges.
+    pub size: Option<Size<u32>>,
+
+    /// The new margin of the surface.
+    pub margin: Option<Padding<i32>>,
+
+    /// The new [`ExclusiveZone`] of the surface.
+    pub exclusive_zone: Option<ExclusiveZone>,
+
+    /// The new [`KeyboardInteractivity`] of the surface.
+    pub keyboard_interactivity: Option<KeyboardInteractivity>,
+}
+
+/// Applies the given [`Changes`] to the layer surface with the given [`Id`]
+/// in a single commit.
+///
+/// Changing the anchor, size and margin through separate actions commits
+/// each of them, so the compositor may configure and show the surface in
+/// every intermediate state. A panel moving to another edge should change
+/// them together instead, so it is configured once.
+pub fn apply<T>(id: Id, changes: Changes) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::ApplyLayerChanges(id, changes))
+}
+
+/// A reduction of the size of a layer surface requested by the compositor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shrink {
+    /// The serial of the configure that shrunk the surface.
+    pub serial: u32,
+    /// The size of the surface before the configure, in logical pixels.
+    pub previous: Size<u32>,
+    /// The size of the surface after the configure, in logical pixels.
+    pub new: Size<u32>,
+}
+
+/// Listens to the compositor shrinking the layer surface with the given
+/// [`Id`] along any dimension, like when an on-screen keyboard appears.
+///
+/// The surface is resized either way; this lets an overlay react to it
+/// (e.g. by scrolling the focused field into view). The [`Task`] produces a
+/// [`Shrink`] for every such configure, until the surface is closed.
+pub fn shrinks(id: Id) -> Task<Shrink> {
+    action::stream(move |channel| action::Action::ListenShrinks(id, channel))
+}
+
+/// Changes the margin of the layer surface with the given [`Id`], the
+/// distance between each of its edges and the anchored edges of the output.
+///
+/// Only the margin is updated, so this is cheap enough to be called every
+/// frame, like to slide a panel in from off-screen. Every change is applied
+/// by the compositor with the next commit of the surface: it is carried by
+/// the next frame if the surface is being redrawn, or committed right away
+/// otherwise. A smooth animation should change the margin once per frame
+/// (e.g. on `RedrawRequested` or with a `window::frames` subscription)
+/// instead of on a timer.
+pub fn set_margin<T>(id: Id, margin: Padding<i32>) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetMargin(id, margin))
+}
+
+/// Moves the layer surface with the given [`Id`] to another [`Layer`].
+///
+/// A bar can move to [`Layer::Overlay`] to stay visible above fullscreen
+/// windows, for instance. This requires version 2 of the layer shell
+/// protocol; older compositors produce a [`SetLayerError`] instead.
+pub fn set_layer(id: Id, layer: Layer) -> Task<Result<(), SetLayerError>> {
+    action::oneshot(move |channel| action::Action::SetLayer(id, layer, channel))
+}
+
+/// Returns whether the layer surface with the given [`Id`] currently holds an
+/// exclusive keyboard grab.
+///
+/// The compositor may refuse an exclusive grab (e.g. for surfaces below the
+/// top layer), in which case this will return `false` even after requesting
+/// [`KeyboardInteractivity::Exclusive`].
+pub fn is_keyboard_grabbed(id: Id) -> Task<bool> {
+    action::oneshot(move |channel| action::Action::GetKeyboardGrab(id, channel))
+}
+
+/// Returns the name of the output the layer surface with the given [`Id`] is
+/// shown on, like `DP-1`.
+///
+/// This is useful for surfaces opened without an output in their
+/// [`Settings`], which the compositor places on an output of its choosing.
+/// The output is only known once the surface has been mapped, so the
+/// [`Task`] waits until it enters one. It produces nothing if there is no such
+/// surface or it is closed before, and `None` if the output has no name.
+///
+/// When the surface spans several outputs, the first one it entered is
+/// returned.
+pub fn output(id: Id) -> Task<Option<String>> {
+    action::oneshot(move |channel| action::Action::GetOutput(id, channel))
+}
+
+/// An error that occurred while opening a layer surface.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OpenError {
+    /// The output requested in the [`Settings`] is not connected.
+    #[error("the output {0:?} was not found")]
+    OutputNotFound(String),
+
+    /// The layer surface was closed before the compositor configured it,
+    /// either by the compositor or by the application itself.
+    #[error("the layer surface was closed before being configured")]
+    Closed,
+}
+
+/// An error that occurred while moving a layer surface to another [`Layer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum SetLayerError {
+    /// The compositor does not support changing the layer of a surface.
+    #[error("the compositor does not support changing the layer of a surface")]
+    Unsupported,
+}
+
+/// Opens a new layer surface with the given [`Settings`], like `open`, but
+/// fails with an [`OpenError`] instead of letting the compositor choose an
+/// output when the requested one is not connected.
+///
+/// Outputs are only looked up when the surface is opened, so this should
+/// not be used for outputs that are expected to be plugged in later.
+pub fn try_open(settings: Settings) -> (Id, Task<Result<Id, OpenError>>) {
+    let id = Id::unique();
+
+    (
+        id,
+        action::oneshot(move |channel| action::Action::OpenLayer(id, settings, channel)),
+    )
+}
+
+/// Opens a copy of the layer surface with the given [`Settings`] on every
+/// output, ignoring [`Settings::output`].
+///
+/// The returned [`Task`] produces the [`Id`] of every surface that is opened,
+/// including the ones opened for outputs that are connected later on. The
+/// surface of an output is closed when the output is disconnected.
+pub fn open_on_all_outputs(settings: Settings) -> Task<Id> {
+    action::stream(move |channel| action::Action::OpenLayerOnAllOutputs(settings, channel))
+}
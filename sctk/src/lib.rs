@@ -1,78 +1,178 @@
 //! A windowing shell for Iced, on top of [`smithay-client-toolkit`].
 
-use std::{rc::Rc, sync::Arc, time::Instant};
+use std::{fmt, mem, rc::Rc, sync::Arc, time::Instant};
 
 use iced_debug::{
-    core::{renderer, widget::operation, window::RedrawRequest, SmolStr},
-    futures::futures::channel::oneshot,
+    core::{widget::operation, window::RedrawRequest, SmolStr},
+    futures::futures::channel::{mpsc, oneshot},
 };
 pub use iced_program as program;
 pub use program::{core, graphics, runtime};
 pub use runtime::{debug, futures};
+pub mod capabilities;
+#[cfg(feature = "dmabuf-feedback")]
+pub mod dmabuf;
+pub mod dnd;
+pub mod surface;
 #[cfg(feature = "system")]
 pub mod system;
 
+mod action;
 mod clipboard;
 mod conversion;
 mod error;
+mod event;
+pub mod font;
+pub mod input_method;
+mod keyboard;
+pub mod keysym;
+pub mod layer_shell;
+pub mod pointer;
 mod proxy;
+pub mod renderer;
+pub mod selection;
+mod settings;
+pub mod subsurface;
+mod text_input;
+pub mod touch;
+pub mod trace;
 mod window;
 
-use core::layer_shell;
-
 use runtime::{user_interface, UserInterface};
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use sctk::{
+    activation::{ActivationHandler, ActivationState, RequestData},
     compositor::{CompositorHandler, CompositorState},
+    data_device_manager::{
+        data_device::{DataDevice, DataDeviceData, DataDeviceHandler},
+        data_offer::{DataOfferHandler, DragOffer},
+        data_source::{CopyPasteSource, DataSourceHandler},
+        DataDeviceManagerState, WritePipe,
+    },
+    globals::ProvidesBoundGlobal,
     output::{OutputHandler, OutputState},
     reexports::{
         calloop::{
+            channel,
+            ping::{make_ping, Ping},
             timer::{TimeoutAction, Timer},
-            Dispatcher, EventLoop, LoopHandle, LoopSignal, RegistrationToken,
+            Dispatcher, EventLoop, LoopHandle, LoopSignal, PostAction, RegistrationToken,
         },
         calloop_wayland_source::WaylandSource,
         client::{
             delegate_noop,
             globals::registry_queue_init,
             protocol::{
-                wl_display, wl_keyboard, wl_output, wl_pointer, wl_seat, wl_surface, wl_touch,
+                wl_data_device, wl_data_device_manager::DndAction, wl_data_source, wl_display,
+                wl_keyboard, wl_output, wl_pointer, wl_seat, wl_surface, wl_touch,
             },
             Connection, Proxy, QueueHandle,
         },
-        protocols::wp::text_input::zv3::client::zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+        protocols::wp::{
+            alpha_modifier::v1::client::{
+                wp_alpha_modifier_surface_v1::WpAlphaModifierSurfaceV1,
+                wp_alpha_modifier_v1::WpAlphaModifierV1,
+            },
+            pointer_constraints::zv1::client::{
+                zwp_confined_pointer_v1::ZwpConfinedPointerV1,
+                zwp_locked_pointer_v1::ZwpLockedPointerV1, zwp_pointer_constraints_v1::Lifetime,
+            },
+            tearing_control::v1::client::{
+                wp_tearing_control_manager_v1::WpTearingControlManagerV1,
+                wp_tearing_control_v1::WpTearingControlV1,
+            },
+            text_input::zv3::client::zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+        },
     },
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
     seat::{
         keyboard::KeyboardHandler,
         pointer::{PointerData, PointerHandler, ThemedPointer},
+        pointer_constraints::{PointerConstraintsHandler, PointerConstraintsState},
         touch::TouchHandler,
         SeatHandler, SeatState,
     },
     shell::{
-        wlr_layer::{self, LayerShell, LayerShellHandler, LayerSurface, LayerSurfaceConfigure},
+        wlr_layer::{LayerShell, LayerShellHandler, LayerSurface, LayerSurfaceConfigure},
         WaylandSurface,
     },
     shm::{Shm, ShmHandler},
+    subcompositor::SubcompositorState,
+};
+#[cfg(feature = "dmabuf-feedback")]
+use sctk::{
+    dmabuf::{DmabufFeedback, DmabufHandler, DmabufState},
+    reexports::{
+        client::protocol::wl_buffer,
+        protocols::wp::linux_dmabuf::zv1::client::{
+            zwp_linux_buffer_params_v1::ZwpLinuxBufferParamsV1,
+            zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1,
+        },
+    },
+};
+#[cfg(feature = "blur")]
+use wayland_protocols_plasma::blur::client::{
+    org_kde_kwin_blur::OrgKdeKwinBlur, org_kde_kwin_blur_manager::OrgKdeKwinBlurManager,
 };
 
-pub use crate::error::Error;
 use crate::{
+    capabilities::Capabilities,
     clipboard::Clipboard,
-    core::{theme, Settings},
+    core::theme,
     futures::{subscription, Executor, Runtime},
     graphics::{compositor, Compositor},
     program::Program,
     proxy::ProxySink,
     runtime::Action,
-    window::{RawWindow, WindowManager},
+    window::{Paste, RawWindow, WindowManager},
+};
+pub use crate::{
+    error::{Error, ShmFormat},
+    settings::Settings,
 };
 
 /// Runs a [`Program`] with the provided settings.
+///
+/// If `window_settings` is `Some`, a main window is opened on startup; a
+/// daemon opens none. The kind of surface of the main window is resolved in
+/// this order:
+///
+/// 1. A normal `xdg_toplevel` window, once the shell supports them. Until
+///    then, this step is always skipped.
+/// 2. A layer surface, with an info message if the compositor supports
+///    normal windows, or a warning if it does not.
+/// 3. Otherwise, [`Error::LayerShellUnsupported`] is returned, since the
+///    shell cannot open any surface.
+///
+/// Any surface opened afterwards through [`layer_shell`] is always a layer
+/// surface, with the same error if it is unsupported.
 pub fn run<P>(
     program: P,
-    settings: Settings,
+    settings: core::Settings,
+    shell_settings: Settings,
+    window_settings: Option<core::window::Settings>,
+) -> Result<(), Error>
+where
+    P: Program + 'static,
+    P::Theme: theme::Base,
+{
+    run_with(program, settings, shell_settings, window_settings, |_| {})
+}
+
+/// Runs a [`Program`] like [`run`], calling `on_start` with the handle of the
+/// event loop before it starts running.
+///
+/// This allows inserting custom `calloop` event sources (e.g. a D-Bus
+/// connection, a timer or a socket) without spawning a thread for each of
+/// them. Their callbacks receive the [`State`] of the shell, which can
+/// [`publish`](State::publish) messages to the [`Program`].
+pub fn run_with<P>(
+    program: P,
+    settings: core::Settings,
+    shell_settings: Settings,
     window_settings: Option<core::window::Settings>,
+    on_start: impl FnOnce(&LoopHandle<'static, State<P>>),
 ) -> Result<(), Error>
 where
     P: Program + 'static,
@@ -93,29 +193,25 @@ where
     let (program, task) = runtime.enter(|| program::Instance::new(program));
     let is_daemon = window_settings.is_none();
 
-    let task = if let Some(_window_settings) = window_settings {
+    let task = if let Some(window_settings) = window_settings {
         let mut task = Some(task);
 
         // HACK: fix after implementing normal windows
         // let (_id, open) = runtime::window::open(window_settings);
 
         let (_id, open) = runtime::layer_shell::open(layer_shell::Settings {
-            layer: core::layer_shell::Layer::Top,
-            namespace: None,
-            size: core::Size {
-                width: 400,
-                height: 400,
-            },
-            anchor: core::layer_shell::Anchor::TOP,
-            exclusive_zone: 0,
+            namespace: shell_settings
+                .application_id
+                .clone()
+                .or(Some(window_settings.platform_specific.application_id)
+                    .filter(|application_id| !application_id.is_empty())),
             margin: core::Padding {
                 top: 200,
                 right: 0,
                 bottom: 0,
                 left: 0,
             },
-            keyboard_interactivity: core::layer_shell::KeyboardInteractivity::OnDemand,
-            output: None,
+            ..layer_shell::anchored(layer_shell::Anchor::TOP, core::Size::new(400, 400))
         });
 
         open.then(move |_| task.take().unwrap_or(runtime::Task::none()))
@@ -123,6 +219,18 @@ where
         task
     };
 
+    // The boot task and the first subscriptions may already perform shell
+    // actions, which are queued until the loop runs.
+    let (action_sender, action_channel) = channel::channel();
+    let _ = loop_handle
+        .insert_source(action_channel, |event, (), state| {
+            if let channel::Event::Msg(action) = event {
+                state.run_shell_action(action);
+            }
+        })
+        .unwrap();
+    let _connected = action::connect(action_sender);
+
     if let Some(stream) = runtime::task::into_stream(task) {
         runtime.run(stream);
     }
@@ -156,24 +264,101 @@ where
         .insert(loop_handle.clone())
         .unwrap();
 
+    let capabilities = Capabilities::detect(&globals);
+    let layer_shell = LayerShell::bind(&globals, &qh).map_err(|_| Error::LayerShellUnsupported)?;
+
+    if !is_daemon {
+        if capabilities.xdg_shell {
+            log::info!(
+                "Normal windows are not supported yet; opening the main window as a layer surface."
+            );
+        } else {
+            log::warn!(
+                "The compositor does not support normal windows (xdg_wm_base); opening the \
+                main window as a layer surface."
+            );
+        }
+    }
+
+    let text_input_manager: Option<ZwpTextInputManagerV3> = globals
+        .bind(&qh, 1..=ZwpTextInputManagerV3::interface().version, ())
+        .ok();
+
+    if text_input_manager.is_none() {
+        // Preedit text is still drawn by the renderer, but without an input
+        // method nothing composes it.
+        log::info!(
+            "The compositor does not support text-input-v3; input methods (e.g. dead keys \
+            and CJK composition) are unavailable."
+        );
+    }
+
+    let alpha_modifier: Option<WpAlphaModifierV1> = globals.bind(&qh, 1..=1, ()).ok();
+    let tearing_control_manager: Option<WpTearingControlManagerV1> =
+        globals.bind(&qh, 1..=1, ()).ok();
+
+    #[cfg(feature = "dmabuf-feedback")]
+    let dmabuf = DmabufState::new(&globals, &qh);
+
+    #[cfg(feature = "blur")]
+    let blur_manager: Option<OrgKdeKwinBlurManager> = globals.bind(&qh, 1..=1, ()).ok();
+
+    let wl_compositor = CompositorState::bind(&globals, &qh).unwrap();
+    let subcompositor =
+        SubcompositorState::bind(wl_compositor.wl_compositor().clone(), &globals, &qh).ok();
+
+    // The clipboard is connected before any surface exists, so its data device
+    // is already bound when the compositor sends the current selection on the
+    // first keyboard focus. Otherwise, an existing selection could be missed
+    // until it changes.
+    #[cfg(feature = "memory-clipboard")]
+    let clipboard = if shell_settings.memory_clipboard {
+        Clipboard::memory()
+    } else {
+        Clipboard::connect(&display)
+    };
+
+    #[cfg(not(feature = "memory-clipboard"))]
+    let clipboard = Clipboard::connect(&display);
+
+    let gestures = shell_settings.touch_gestures.map(touch::Recognizer::new);
+
     let mut state = State {
         conn,
         display,
         registry_state: RegistryState::new(&globals),
         output_state: OutputState::new(&globals, &qh),
         seat_state: SeatState::new(&globals, &qh),
-        wl_compositor: CompositorState::bind(&globals, &qh).unwrap(),
+        wl_compositor,
+        subcompositor,
         shm: Shm::bind(&globals, &qh).unwrap(),
-        layer_shell: LayerShell::bind(&globals, &qh).unwrap(),
-        text_input_manager: globals.bind(&qh, 1..=1, ()).ok(),
+        layer_shell,
+        activation: ActivationState::bind(&globals, &qh).ok(),
+        data_device_manager: DataDeviceManagerState::bind(&globals, &qh).ok(),
+        pointer_constraints: PointerConstraintsState::bind(&globals, &qh),
+        #[cfg(feature = "dmabuf-feedback")]
+        dmabuf,
+        capabilities: Capabilities {
+            text_input: text_input_manager.is_some(),
+            alpha_modifier: alpha_modifier.is_some(),
+            tearing_control: tearing_control_manager.is_some(),
+            ..capabilities
+        },
+        text_input_manager,
+        alpha_modifier,
+        tearing_control_manager,
+        #[cfg(feature = "blur")]
+        blur_manager,
         qh,
 
         keyboards: FxHashMap::default(),
         pointers: FxHashMap::default(),
         touch: FxHashMap::default(),
+        data_devices: FxHashMap::default(),
 
-        keyboard_focuses: FxHashMap::default(),
+        keyboard_focuses: keyboard::Focuses::default(),
         touch_focuses: FxHashMap::default(),
+        gestures,
 
         is_daemon,
         error: None,
@@ -183,6 +368,7 @@ where
         loop_timer_token,
 
         settings,
+        shell_settings,
 
         runtime,
         program_wrapper: Some(
@@ -193,28 +379,178 @@ where
             .build(),
         ),
         compositor: None,
+        compositor_source: None,
 
         window_manager: WindowManager::new(),
-        clipboard: Clipboard::unconnected(),
+        clipboard,
         messages: Vec::new(),
         events: Vec::new(),
         actions: 0,
         in_progress_windows: FxHashMap::default(),
+        output_groups: Vec::new(),
+        modifier_order: keyboard::ModifierOrder::default(),
+        keysym_listeners: Vec::new(),
+        pointer_listeners: Vec::new(),
+        file_drop_listeners: Vec::new(),
+        selection_lost_listeners: Vec::new(),
+        selection_source: None,
+        last_input: None,
+        alt_gr: keyboard::AltGr::default(),
+        pending_fonts: Vec::new(),
+        text_inputs: FxHashMap::default(),
     };
 
+    log::debug!(
+        "Bound the globals of the compositor: {:?}.",
+        state.capabilities
+    );
+
+    on_start(&state.loop_handle);
+
     boot_span.finish();
 
-    let _ = event_loop.run(None, &mut state, |state| {
+    let result = event_loop.run(None, &mut state, |state| {
         state.about_to_wait();
     });
 
+    // The Wayland source fails once the connection to the compositor is
+    // lost, which ends the loop without going through `exit`.
+    if let Err(error) = result {
+        match state.conn.protocol_error() {
+            Some(protocol_error) => {
+                log::error!("Connection to the compositor lost: {protocol_error}.");
+            }
+            None => log::error!("Connection to the compositor lost: {error}."),
+        }
+
+        if state.error.is_none() {
+            state.error = Some(Error::ConnectionLost);
+        }
+    }
+
     state.error.map(Err).unwrap_or(Ok(()))
 }
 
+/// The format of the `wl_shm` buffers presented by the software renderer.
+const SOFTWARE_SHM_FORMAT: ShmFormat = ShmFormat::Xrgb8888;
+
+/// Returns the windows to redraw after an update of the [`Program`], given
+/// whether each window changed when it was synchronized.
+///
+/// Only the windows that were affected by the update or that changed are
+/// redrawn if the setting is enabled, unless some messages did not come from
+/// any window.
+fn redrawn_windows(
+    redraw_affected_windows_only: bool,
+    has_unattributed_messages: bool,
+    affected: &FxHashSet<core::window::Id>,
+    synchronized: impl IntoIterator<Item = (core::window::Id, bool)>,
+) -> Vec<core::window::Id> {
+    let redraws_all = !redraw_affected_windows_only || has_unattributed_messages;
+
+    synchronized
+        .into_iter()
+        .filter(|(id, changed)| redraws_all || *changed || affected.contains(id))
+        .map(|(id, _)| id)
+        .collect()
+}
+
+/// Returns how the loop timer is rearmed after it fires.
+///
+/// The timer fires once right after startup, since it is created with
+/// [`Timer::immediate`]. Without a deadline it is then dropped, instead of
+/// firing again, so the loop blocks until an event source wakes it up, and
+/// it is only armed again by `schedule_wake_if_needed`.
+fn timeout_action(wake_at: Option<Instant>) -> TimeoutAction {
+    match wake_at {
+        Some(wake_at) => TimeoutAction::ToInstant(wake_at),
+        None => TimeoutAction::Drop,
+    }
+}
+
 struct InProgressWindow {
     id: core::window::Id,
     raw_window: RawWindow,
-    sender: oneshot::Sender<core::window::Id>,
+    layer_settings: Option<core::layer_shell::Settings>,
+    sender: OpenSender,
+    /// The size of the last configure, if the window is waiting for the
+    /// compositor to be created.
+    configured: Option<core::Size<u32>>,
+    /// The requests for the output of the window, which is only known once
+    /// it is mapped.
+    output_requests: Vec<oneshot::Sender<Option<String>>>,
+}
+
+impl From<InProgressWindow> for (core::window::Id, OpenSender) {
+    fn from(window: InProgressWindow) -> Self {
+        (window.id, window.sender)
+    }
+}
+
+/// Removes the window in progress of a surface that was closed before being
+/// configured, resolving its open task.
+fn cancel_in_progress<K, W>(
+    in_progress_windows: &mut FxHashMap<K, W>,
+    surface: &K,
+) -> Option<core::window::Id>
+where
+    K: Eq + std::hash::Hash,
+    W: Into<(core::window::Id, OpenSender)>,
+{
+    let (id, sender) = in_progress_windows.remove(surface)?.into();
+
+    log::debug!("Layer surface {id:?} was closed before being configured.");
+    sender.cancel();
+
+    Some(id)
+}
+
+/// Wakes the event loop to poll the creation of the compositor again.
+struct PingWaker(Ping);
+
+impl std::task::Wake for PingWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.ping();
+    }
+}
+
+/// The channel that is resolved once a window has been opened.
+enum OpenSender {
+    Runtime(oneshot::Sender<core::window::Id>),
+    Shell(oneshot::Sender<Result<core::window::Id, layer_shell::OpenError>>),
+    Group(mpsc::UnboundedSender<core::window::Id>),
+}
+
+impl OpenSender {
+    fn send(self, id: core::window::Id) {
+        match self {
+            OpenSender::Runtime(sender) => {
+                let _ = sender.send(id);
+            }
+            OpenSender::Shell(sender) => {
+                let _ = sender.send(Ok(id));
+            }
+            OpenSender::Group(sender) => {
+                let _ = sender.unbounded_send(id);
+            }
+        }
+    }
+
+    /// Resolves the channel of a window that will never be opened.
+    ///
+    /// The tasks of the runtime produce nothing in that case.
+    fn cancel(self) {
+        if let OpenSender::Shell(sender) = self {
+            let _ = sender.send(Err(layer_shell::OpenError::Closed));
+        }
+    }
+}
+
+/// A layer surface that is opened on every output.
+struct OutputGroup {
+    settings: core::layer_shell::Settings,
+    sender: mpsc::UnboundedSender<core::window::Id>,
+    surfaces: Vec<(wl_output::WlOutput, core::window::Id)>,
 }
 
 #[ouroboros::self_referencing]
@@ -229,7 +565,11 @@ where
         FxHashMap<core::window::Id, UserInterface<'this, P::Message, P::Theme, P::Renderer>>,
 }
 
-struct State<P>
+/// The state of a running shell.
+///
+/// It is only accessible from the callbacks of custom event sources
+/// inserted with [`run_with`].
+pub struct State<P>
 where
     P: Program + 'static,
 {
@@ -240,16 +580,29 @@ where
     output_state: OutputState,
     seat_state: SeatState,
     wl_compositor: CompositorState,
+    subcompositor: Option<SubcompositorState>,
     shm: Shm,
     layer_shell: LayerShell,
+    activation: Option<ActivationState>,
+    data_device_manager: Option<DataDeviceManagerState>,
+    pointer_constraints: PointerConstraintsState,
+    #[cfg(feature = "dmabuf-feedback")]
+    dmabuf: DmabufState,
     text_input_manager: Option<ZwpTextInputManagerV3>,
+    alpha_modifier: Option<WpAlphaModifierV1>,
+    tearing_control_manager: Option<WpTearingControlManagerV1>,
+    #[cfg(feature = "blur")]
+    blur_manager: Option<OrgKdeKwinBlurManager>,
+    capabilities: Capabilities,
 
     keyboards: FxHashMap<wl_seat::WlSeat, wl_keyboard::WlKeyboard>,
     pointers: FxHashMap<wl_seat::WlSeat, Rc<ThemedPointer>>,
     touch: FxHashMap<wl_seat::WlSeat, wl_touch::WlTouch>,
+    data_devices: FxHashMap<wl_seat::WlSeat, DataDevice>,
 
-    keyboard_focuses: FxHashMap<wl_keyboard::WlKeyboard, core::window::Id>,
+    keyboard_focuses: keyboard::Focuses<wl_keyboard::WlKeyboard>,
     touch_focuses: FxHashMap<wl_touch::WlTouch, FxHashMap<i32, (core::window::Id, core::Point)>>,
+    gestures: Option<touch::Recognizer>,
 
     is_daemon: bool,
     error: Option<Error>,
@@ -259,10 +612,12 @@ where
     loop_timer_token: RegistrationToken,
 
     settings: core::Settings,
+    shell_settings: Settings,
 
     runtime: Runtime<P::Executor, ProxySink<P::Message>, Action<P::Message>>,
     program_wrapper: Option<ProgramWrapper<P>>,
     compositor: Option<<P::Renderer as compositor::Default>::Compositor>,
+    compositor_source: Option<RegistrationToken>,
 
     window_manager: WindowManager<P>,
     clipboard: Clipboard,
@@ -271,42 +626,109 @@ where
     actions: usize,
 
     in_progress_windows: FxHashMap<wl_surface::WlSurface, InProgressWindow>,
+    output_groups: Vec<OutputGroup>,
+    modifier_order: keyboard::ModifierOrder,
+    keysym_listeners: Vec<mpsc::UnboundedSender<keysym::Event>>,
+    pointer_listeners: Vec<mpsc::UnboundedSender<pointer::Event>>,
+    file_drop_listeners: Vec<mpsc::UnboundedSender<dnd::FilesDropped>>,
+    selection_lost_listeners: Vec<mpsc::UnboundedSender<()>>,
+    /// The data source of the standard selection, while the shell owns it.
+    selection_source: Option<CopyPasteSource>,
+    /// The seat and serial of the last input event, which the selection is
+    /// set with.
+    last_input: Option<(wl_seat::WlSeat, u32)>,
+    alt_gr: keyboard::AltGr<wl_keyboard::WlKeyboard>,
+    pending_fonts: Vec<std::borrow::Cow<'static, [u8]>>,
+    text_inputs: FxHashMap<wl_seat::WlSeat, text_input::TextInput>,
+}
+
+impl<P: Program + 'static> fmt::Debug for State<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("State")
+            .field("is_daemon", &self.is_daemon)
+            .field("capabilities", &self.capabilities)
+            .field("shell_settings", &self.shell_settings)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<P: Program + 'static> State<P> {
+    /// Publishes a message to the [`Program`], as if it was produced by one
+    /// of its tasks or subscriptions.
+    pub fn publish(&mut self, message: P::Message) {
+        self.messages.push(message);
+    }
+
     fn open_layer(
         &mut self,
         id: core::window::Id,
         settings: core::layer_shell::Settings,
-        sender: oneshot::Sender<core::window::Id>,
+        sender: OpenSender,
     ) {
         let output = match &settings.output {
-            Some(name) => self.output_state.outputs().find(|output| {
-                self.output_state
-                    .info(output)
-                    .and_then(|output| output.name)
-                    .is_some_and(|n| n.eq(name))
-            }),
+            Some(name) => {
+                let output = self.output_state.outputs().find(|output| {
+                    self.output_state
+                        .info(output)
+                        .and_then(|output| output.name)
+                        .is_some_and(|n| n.eq(name))
+                });
+
+                if output.is_none() {
+                    match sender {
+                        OpenSender::Shell(sender) => {
+                            let _ = sender
+                                .send(Err(layer_shell::OpenError::OutputNotFound(name.clone())));
+                            return;
+                        }
+                        OpenSender::Runtime(_) => {
+                            log::warn!(
+                                "Output {name:?} not found when opening layer surface {id:?}, \
+                                letting the compositor choose one."
+                            );
+                        }
+                        OpenSender::Group(_) => {
+                            // The outputs of a group are picked by name, so
+                            // this one vanished in the meantime.
+                            log::warn!(
+                                "Output {name:?} of layer surface {id:?} vanished, \
+                                skipping it."
+                            );
+                            return;
+                        }
+                    }
+                }
+
+                output
+            }
             None => None,
         };
 
+        self.create_layer_surface(id, settings, output.as_ref(), sender);
+    }
+
+    fn create_layer_surface(
+        &mut self,
+        id: core::window::Id,
+        settings: core::layer_shell::Settings,
+        output: Option<&wl_output::WlOutput>,
+        sender: OpenSender,
+    ) {
         let surface = self.wl_compositor.create_surface(&self.qh);
 
         let layer_surface = self.layer_shell.create_layer_surface(
             &self.qh,
             surface.clone(),
-            match settings.layer {
-                core::layer_shell::Layer::Background => wlr_layer::Layer::Background,
-                core::layer_shell::Layer::Bottom => wlr_layer::Layer::Bottom,
-                core::layer_shell::Layer::Top => wlr_layer::Layer::Top,
-                core::layer_shell::Layer::Overlay => wlr_layer::Layer::Overlay,
-            },
-            settings.namespace,
-            output.as_ref(),
+            conversion::layer_shell::layer(settings.layer),
+            settings
+                .namespace
+                .clone()
+                .or_else(|| self.shell_settings.application_id.clone()),
+            output,
         );
 
         layer_surface.set_size(settings.size.width, settings.size.height);
-        layer_surface.set_anchor(wlr_layer::Anchor::from_bits(settings.anchor.bits()).unwrap());
+        layer_surface.set_anchor(conversion::layer_shell::anchor(settings.anchor));
         layer_surface.set_exclusive_zone(settings.exclusive_zone);
         layer_surface.set_margin(
             settings.margin.top,
@@ -314,31 +736,71 @@ impl<P: Program + 'static> State<P> {
             settings.margin.bottom,
             settings.margin.left,
         );
-        layer_surface.set_keyboard_interactivity(match settings.keyboard_interactivity {
-            core::layer_shell::KeyboardInteractivity::None => {
-                wlr_layer::KeyboardInteractivity::None
-            }
-            core::layer_shell::KeyboardInteractivity::Exclusive => {
-                wlr_layer::KeyboardInteractivity::Exclusive
-            }
-            core::layer_shell::KeyboardInteractivity::OnDemand => {
-                wlr_layer::KeyboardInteractivity::OnDemand
-            }
-        });
+        layer_surface.set_keyboard_interactivity(conversion::layer_shell::keyboard_interactivity(
+            settings.keyboard_interactivity,
+        ));
 
         layer_surface.commit();
 
+        log::debug!(
+            "Created layer surface {id:?} on {:?} (output: {:?}).",
+            settings.layer,
+            settings.output
+        );
+
         let _ = self.in_progress_windows.insert(
             surface,
             InProgressWindow {
                 id,
                 raw_window: RawWindow::Layer(self.display.clone(), layer_surface),
+                layer_settings: Some(settings),
                 sender,
+                configured: None,
+                output_requests: Vec::new(),
             },
         );
     }
 
+    fn open_layer_on_output(&mut self, group: &mut OutputGroup, output: wl_output::WlOutput) {
+        let id = core::window::Id::unique();
+        let settings = core::layer_shell::Settings {
+            output: self.output_state.info(&output).and_then(|info| info.name),
+            ..group.settings.clone()
+        };
+
+        self.create_layer_surface(
+            id,
+            settings,
+            Some(&output),
+            OpenSender::Group(group.sender.clone()),
+        );
+        group.surfaces.push((output, id));
+    }
+
+    /// Drops a window that has not been configured yet, destroying its layer
+    /// surface and resolving its open task.
+    fn cancel_in_progress_window(&mut self, surface: &wl_surface::WlSurface) {
+        if let Some(id) = cancel_in_progress(&mut self.in_progress_windows, surface) {
+            for group in &mut self.output_groups {
+                group.surfaces.retain(|(_, surface)| *surface != id);
+            }
+        }
+    }
+
     fn close_window(&mut self, id: core::window::Id) {
+        log::debug!("Closing window {id:?}.");
+
+        // A window may be closed before the compositor configures it.
+        if let Some(surface) = self
+            .in_progress_windows
+            .iter()
+            .find(|(_, window)| window.id == id)
+            .map(|(surface, _)| surface.clone())
+        {
+            self.cancel_in_progress_window(&surface);
+            return;
+        }
+
         if !self.is_daemon && self.in_progress_windows.is_empty() && self.window_manager.is_empty()
         {
             self.exit(None);
@@ -352,9 +814,23 @@ impl<P: Program + 'static> State<P> {
                 .with_user_interfaces_mut(|user_interfaces| user_interfaces.remove(&id));
         }
 
-        if let Some(_window) = self.window_manager.remove(id) {
+        for group in &mut self.output_groups {
+            group.surfaces.retain(|(_, surface)| *surface != id);
+        }
+
+        if let Some(window) = self.window_manager.remove(id) {
             // TODO: handle clipboard stuff here, if needed
 
+            window.destroy();
+
+            if let Err(error) = self.conn.flush() {
+                log::error!("Error {error:?} when destroying window {id:?}.");
+            }
+
+            if let Some(error) = self.conn.protocol_error() {
+                log::error!("Protocol error {error} after destroying window {id:?}.");
+            }
+
             self.events
                 .push((id, core::Event::Window(core::window::Event::Closed)));
         }
@@ -365,25 +841,57 @@ impl<P: Program + 'static> State<P> {
     }
 
     fn on_timer_wake(&mut self, now: Instant) -> TimeoutAction {
+        let mut overdue = Vec::new();
+
         for (_, window) in self.window_manager.iter_mut() {
+            if window.is_first_frame_overdue(now) {
+                overdue.push(window.raw.surface().clone());
+            }
+
             if let RedrawRequest::At(redraw_at) = window.redraw_at {
                 if redraw_at <= now {
                     window.request_redraw(core::window::RedrawRequest::NextFrame);
                 }
             }
+
+            window.hide_idle_cursor(now);
+            window.resume_expired_redraws(now);
+            window.detect_occlusion(now);
         }
 
-        if let Some(redraw_at) = self.window_manager.redraw_at() {
-            TimeoutAction::ToInstant(redraw_at)
-        } else {
-            TimeoutAction::Drop
+        // The window is drawn as if its first frame callback arrived, so its
+        // contents are submitted at least once.
+        if !overdue.is_empty() {
+            let (conn, qh) = (self.conn.clone(), self.qh.clone());
+
+            for surface in overdue {
+                CompositorHandler::frame(self, &conn, &qh, &surface, 0);
+            }
+        }
+
+        if let Some(gestures) = &mut self.gestures {
+            gestures.expire(now, &mut self.events);
         }
+
+        timeout_action(self.wake_at())
+    }
+
+    /// Returns the next instant at which the shell needs to be woken up.
+    fn wake_at(&self) -> Option<Instant> {
+        let gesture_at = self.gestures.as_ref().and_then(touch::Recognizer::deadline);
+
+        self.window_manager
+            .wake_at()
+            .into_iter()
+            .chain(gesture_at)
+            .min()
     }
 
     fn schedule_wake_if_needed(&mut self) {
+        let wake_at = self.wake_at();
         let mut loop_timer = self.loop_timer_handle.as_source_mut();
 
-        if let Some(new) = self.window_manager.redraw_at()
+        if let Some(new) = wake_at
             && loop_timer
                 .current_deadline()
                 .is_none_or(|current| current > new)
@@ -399,12 +907,18 @@ impl<P: Program + 'static> State<P> {
             self.actions = 0;
         }
 
+        self.modifier_order.flush(&mut self.events);
+
         if self.events.is_empty() && self.messages.is_empty() && self.window_manager.is_idle() {
             return;
         }
 
         let mut uis_stale = false;
 
+        // Messages of tasks and subscriptions may concern any window.
+        let has_unattributed_messages = !self.messages.is_empty();
+        let mut affected = FxHashSet::default();
+
         let program_wrapper = self.program_wrapper.as_mut().unwrap();
 
         for (id, window) in self.window_manager.iter_mut() {
@@ -422,24 +936,40 @@ impl<P: Program + 'static> State<P> {
                 }
             });
 
-            if window_events.is_empty() && self.messages.is_empty() {
+            if window_events.is_empty() && self.messages.is_empty() && window.paste.is_none() {
                 continue;
             }
 
+            if !self.shell_settings.every_cursor_move {
+                event::coalesce_cursor_moves(&mut window_events);
+            }
+
+            let published = self.messages.len();
+            let paste = window.paste.take().and_then(|paste| match paste {
+                Paste::Primary(position) => {
+                    core::Clipboard::read(&self.clipboard, core::clipboard::Kind::Primary)
+                        .map(|contents| (position, contents))
+                }
+                Paste::Dropped(position, text) => Some((position, text)),
+            });
+
             let (ui_state, statuses) =
                 program_wrapper.with_user_interfaces_mut(|user_interfaces| {
-                    user_interfaces
-                        .get_mut(&id)
-                        .expect("Get user interface")
-                        .update(
-                            &window_events,
-                            window.state.cursor(),
-                            &mut window.renderer,
-                            &mut self.clipboard,
-                            &mut self.messages,
-                        )
+                    update_user_interface(
+                        user_interfaces.get_mut(&id).expect("Get user interface"),
+                        &mut window_events,
+                        paste,
+                        window.state.cursor(),
+                        &mut window.renderer,
+                        &mut self.clipboard,
+                        &mut self.messages,
+                    )
                 });
 
+            if !window_events.is_empty() || self.messages.len() > published {
+                let _ = affected.insert(id);
+            }
+
             match ui_state {
                 user_interface::State::Updated {
                     redraw_request,
@@ -451,6 +981,7 @@ impl<P: Program + 'static> State<P> {
                 }
                 user_interface::State::Outdated => {
                     uis_stale = true;
+                    let _ = affected.insert(id);
                 }
             }
 
@@ -473,6 +1004,9 @@ impl<P: Program + 'static> State<P> {
             });
         }
 
+        // Widgets may have copied text while processing their events.
+        self.offer_selection();
+
         if !self.messages.is_empty() || uis_stale {
             let mut program_wrapper = self.program_wrapper.take().unwrap();
 
@@ -499,9 +1033,21 @@ impl<P: Program + 'static> State<P> {
 
             self.runtime.track(recipes);
 
-            for (id, window) in self.window_manager.iter_mut() {
-                window.state.synchronize(&program, id);
-                window.request_redraw(core::window::RedrawRequest::NextFrame);
+            let synchronized: Vec<_> = self
+                .window_manager
+                .iter_mut()
+                .map(|(id, window)| (id, window.state.synchronize(&program, id)))
+                .collect();
+
+            for id in redrawn_windows(
+                self.shell_settings.redraw_affected_windows_only,
+                has_unattributed_messages,
+                &affected,
+                synchronized,
+            ) {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.request_redraw(core::window::RedrawRequest::NextFrame);
+                }
             }
 
             debug::theme_changed(|| {
@@ -541,8 +1087,7 @@ impl<P: Program + 'static> State<P> {
     }
 
     fn run_action(&mut self, action: Action<P::Message>) {
-        // use crate::runtime::clipboard;
-        use crate::runtime::{layer_shell, system};
+        use crate::runtime::{clipboard, layer_shell, system, window};
         // use crate::runtime::window;
 
         self.actions += 1;
@@ -550,11 +1095,58 @@ impl<P: Program + 'static> State<P> {
             Action::Output(message) => {
                 self.messages.push(message);
             }
-            Action::Clipboard(_action) => todo!(),
-            Action::Window(_action) => todo!(),
+            Action::Clipboard(action) => match action {
+                clipboard::Action::Read { target, channel } => {
+                    let _ = channel.send(core::Clipboard::read(&self.clipboard, target));
+                }
+                clipboard::Action::Write { target, contents } => {
+                    core::Clipboard::write(&mut self.clipboard, target, contents);
+                    self.offer_selection();
+                }
+            },
+            Action::Window(action) => match action {
+                window::Action::GainFocus(id) => {
+                    self.focus_window(id);
+                }
+                window::Action::RequestUserAttention(id, attention) => {
+                    // The attention request is cleared by the compositor once
+                    // the window is focused; it cannot be withdrawn earlier.
+                    if attention.is_some() {
+                        self.activate_window(id);
+                    }
+                }
+                window::Action::Screenshot(id, channel) => {
+                    // The renderer still holds the primitives of the last
+                    // frame, which are drawn again into an offscreen buffer
+                    // (a texture readback with `wgpu`, a buffer copy with
+                    // `tiny-skia`) instead of reading the presented buffer.
+                    if let Some(window) = self.window_manager.get_mut(id)
+                        && let Some(compositor) = &mut self.compositor
+                    {
+                        let bytes = compositor.screenshot(
+                            &mut window.renderer,
+                            window.state.viewport(),
+                            window.state.background_color(),
+                        );
+
+                        let _ = channel.send(core::window::Screenshot::new(
+                            bytes,
+                            window.state.physical_size(),
+                            window.state.viewport().scale_factor(),
+                        ));
+                    }
+                }
+                // The other actions apply to normal windows, which are not
+                // supported yet. The ignored action is dropped, closing its
+                // channel, so a task waiting on a reply ends instead of
+                // hanging.
+                _ => {
+                    log::warn!("Ignoring a window action that layer surfaces do not support.");
+                }
+            },
             Action::LayerShell(action) => match action {
                 layer_shell::Action::Open(id, settings, sender) => {
-                    self.open_layer(id, settings, sender);
+                    self.open_layer(id, settings, OpenSender::Runtime(sender));
                 }
                 layer_shell::Action::Close(id) => {
                     self.close_window(id);
@@ -599,12 +1191,16 @@ impl<P: Program + 'static> State<P> {
                 });
             }
             Action::LoadFont { bytes, channel } => {
-                if let Some(compositor) = &mut self.compositor {
-                    // TODO: Error handling (?)
-                    compositor.load_font(bytes.clone());
-
-                    let _ = channel.send(Ok(()));
+                // TODO: Error handling (?)
+                match &mut self.compositor {
+                    Some(compositor) => compositor.load_font(bytes),
+                    // The font is loaded along with the fonts of the settings
+                    // as soon as the compositor is created, before any window
+                    // is drawn, so it is as good as loaded already.
+                    None => self.pending_fonts.push(bytes),
                 }
+
+                let _ = channel.send(Ok(()));
             }
             Action::Reload => {
                 let program_wrapper = self.program_wrapper.as_mut().unwrap();
@@ -636,55 +1232,1069 @@ impl<P: Program + 'static> State<P> {
         }
     }
 
-    fn exit(&mut self, error: Option<Error>) {
-        self.error = error;
-        self.loop_signal.stop();
-        self.loop_signal.wakeup();
-    }
-}
+    fn run_shell_action(&mut self, action: action::Action) {
+        self.actions += 1;
+        match action {
+            action::Action::SetTheme(id, theme) => {
+                let theme = match theme.map(|theme| theme.downcast::<P::Theme>()) {
+                    Some(Ok(theme)) => Some(*theme),
+                    Some(Err(_)) => {
+                        log::warn!("Theme override for window {id:?} has the wrong type.");
+                        return;
+                    }
+                    None => None,
+                };
 
-/// Builds a window's [`UserInterface`] for the [`Program`].
-fn build_user_interface<'a, P: Program + 'static>(
-    program: &'a program::Instance<P>,
-    cache: user_interface::Cache,
-    renderer: &mut P::Renderer,
-    size: core::Size,
-    id: core::window::Id,
-) -> UserInterface<'a, P::Message, P::Theme, P::Renderer>
-where
-    P::Theme: theme::Base,
-{
-    let view_span = debug::view(id);
-    let view = program.view(id);
-    view_span.finish();
+                let program = self.program_wrapper.as_ref().unwrap().borrow_program();
 
-    let layout_span = debug::layout(id);
-    let user_interface = UserInterface::build(view, size, cache, renderer);
-    layout_span.finish();
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.state.override_theme(program, id, theme);
+                    window.request_redraw(RedrawRequest::NextFrame);
+                }
+            }
+            action::Action::SetScaleFactor(id, scale_factor) => {
+                let program = self.program_wrapper.as_ref().unwrap().borrow_program();
 
-    user_interface
-}
+                if let Some(window) = self.window_manager.get_mut(id)
+                    && window
+                        .state
+                        .override_scale_factor(program, id, scale_factor)
+                {
+                    window.request_redraw(RedrawRequest::NextFrame);
+                    self.events.push((
+                        id,
+                        core::Event::Window(core::window::Event::Resized(
+                            window.state.viewport().logical_size(),
+                        )),
+                    ));
+                }
+            }
+            action::Action::SetKeyboardInteractivity(id, keyboard_interactivity) => {
+                // The interactivity chosen by the program is kept when the
+                // window loses the focus.
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.raised_interactivity = false;
+                }
 
-sctk::delegate_compositor!(@<P: Program + 'static> State<P>);
-sctk::delegate_keyboard!(@<P: Program + 'static> State<P>);
-sctk::delegate_layer!(@<P: Program + 'static> State<P>);
-sctk::delegate_output!(@<P: Program + 'static> State<P>);
-sctk::delegate_pointer!(@<P: Program + 'static> State<P>);
-sctk::delegate_registry!(@<P: Program + 'static> State<P>);
-sctk::delegate_seat!(@<P: Program + 'static> State<P>);
-sctk::delegate_shm!(@<P: Program + 'static> State<P>);
-sctk::delegate_touch!(@<P: Program + 'static> State<P>);
+                self.set_keyboard_interactivity(id, keyboard_interactivity);
+            }
+            action::Action::SetExclusiveZone(id, exclusive_zone) => {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.set_exclusive_zone(exclusive_zone);
+                }
+            }
+            action::Action::ApplyLayerChanges(id, changes) => {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.apply_layer_changes(changes);
+                }
+            }
+            action::Action::GetThrottled(id, channel) => {
+                if let Some(window) = self.window_manager.get(id) {
+                    let _ = channel.send(window.is_throttled(Instant::now()));
+                }
+            }
+            action::Action::ListenVisibility(id, sender) => {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.visibility_listeners.push(sender);
+                }
+            }
+            #[cfg(feature = "dmabuf-feedback")]
+            action::Action::ListenDmabufFeedback(id, sender) => {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.listen_dmabuf_feedback(&self.dmabuf, sender);
+                }
+            }
+            action::Action::ListenKeysyms(sender) => {
+                self.keysym_listeners.push(sender);
+            }
+            action::Action::ListenPointerButtons(sender) => {
+                self.pointer_listeners.push(sender);
+            }
+            action::Action::ListenDroppedFiles(sender) => {
+                self.file_drop_listeners.push(sender);
+            }
+            action::Action::ListenSelectionLost(sender) => {
+                self.selection_lost_listeners.push(sender);
+            }
+            action::Action::ListenShrinks(id, sender) => {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.shrink_listeners.push(sender);
+                }
+            }
+            action::Action::SetMargin(id, margin) => {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.set_margin(margin);
+                }
+            }
+            action::Action::SetLayer(id, layer, channel) => {
+                // `set_layer` was introduced in version 2 of the protocol.
+                let result = if self
+                    .layer_shell
+                    .bound_global()
+                    .is_ok_and(|layer_shell| layer_shell.version() >= 2)
+                {
+                    if let Some(window) = self.window_manager.get_mut(id) {
+                        window.set_layer(layer);
+                    }
 
-delegate_noop!(@<P: Program + 'static> State<P>: ZwpTextInputManagerV3);
+                    Ok(())
+                } else {
+                    Err(layer_shell::SetLayerError::Unsupported)
+                };
+
+                let _ = channel.send(result);
+            }
+            action::Action::GetKeyboardGrab(id, channel) => {
+                let is_focused = self.keyboard_focuses.is_focused(id);
+                let is_exclusive = self
+                    .window_manager
+                    .get(id)
+                    .and_then(|window| window.layer_settings.as_ref())
+                    .is_some_and(|settings| {
+                        matches!(
+                            settings.keyboard_interactivity,
+                            core::layer_shell::KeyboardInteractivity::Exclusive
+                        )
+                    });
+
+                let _ = channel.send(is_focused && is_exclusive);
+            }
+            action::Action::GetOutput(id, channel) => {
+                // The channel is dropped for unknown windows, so the task
+                // produces nothing.
+                let Some(window) = self.window_manager.get_mut(id) else {
+                    if let Some(window) = self
+                        .in_progress_windows
+                        .values_mut()
+                        .find(|window| window.id == id)
+                    {
+                        window.output_requests.push(channel);
+                    }
+
+                    return;
+                };
+
+                match window.outputs.first() {
+                    Some(output) => {
+                        let name = self.output_state.info(output).and_then(|info| info.name);
+                        let _ = channel.send(name);
+                    }
+                    None => window.output_requests.push(channel),
+                }
+            }
+            action::Action::WaitPresent(id, channel) => {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.present_requests.push(channel);
+                    window.request_redraw(core::window::RedrawRequest::NextFrame);
+                }
+            }
+            action::Action::SetSurroundingText(id, surrounding_text) => {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.surrounding_text = surrounding_text;
+                    self.sync_text_inputs();
+                }
+            }
+            action::Action::Commit(id) => {
+                if let Some(window) = self.window_manager.get(id) {
+                    window.raw.surface().commit();
+                }
+            }
+            action::Action::GetModifiers(channel) => {
+                let modifiers = self
+                    .keyboard_focuses
+                    .windows()
+                    .filter_map(|id| self.window_manager.get(id))
+                    .map(|window| conversion::keyboard::modifiers(window.state.modifiers()))
+                    .fold(core::keyboard::Modifiers::empty(), |all, modifiers| {
+                        all | modifiers
+                    });
+
+                let _ = channel.send(modifiers);
+            }
+            action::Action::ListFontFamilies(channel) => {
+                let _ = channel.send(font::loaded_families());
+            }
+            action::Action::GetCapabilities(channel) => {
+                let _ = channel.send(self.capabilities);
+            }
+            action::Action::GetRenderer(channel) => {
+                let renderer = self
+                    .compositor
+                    .as_mut()
+                    .map(|compositor| compositor.fetch_information().into());
+
+                let _ = channel.send(renderer);
+            }
+            action::Action::OpenLayer(id, settings, sender) => {
+                self.open_layer(id, settings, OpenSender::Shell(sender));
+            }
+            action::Action::OpenLayerOnAllOutputs(settings, sender) => {
+                let mut group = OutputGroup {
+                    settings,
+                    sender,
+                    surfaces: Vec::new(),
+                };
+
+                for output in self.output_state.outputs().collect::<Vec<_>>() {
+                    self.open_layer_on_output(&mut group, output);
+                }
+
+                self.output_groups.push(group);
+            }
+            action::Action::SetCursorAutoHide(id, timeout) => {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.set_cursor_auto_hide(timeout);
+                    self.schedule_wake_if_needed();
+                }
+            }
+            action::Action::SuspendRedraws(id, timeout) => {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.suspend_redraws(timeout);
+                    self.schedule_wake_if_needed();
+                }
+            }
+            action::Action::SetMinRedrawInterval(id, interval) => {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.set_min_redraw_interval(interval);
+                }
+            }
+            action::Action::ResumeRedraws(id) => {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.resume_redraws();
+                }
+            }
+            action::Action::CreateSubsurface(parent, position, channel) => {
+                let _ = channel.send(self.create_subsurface(parent, position));
+            }
+            action::Action::SetSubsurfacePosition(id, position) => {
+                for (_, window) in self.window_manager.iter_mut() {
+                    if let Some(subsurface) = window.subsurfaces.get(&id) {
+                        subsurface.set_position(position);
+                        window.request_redraw(RedrawRequest::NextFrame);
+                        break;
+                    }
+                }
+            }
+            action::Action::DestroySubsurface(id) => {
+                for (_, window) in self.window_manager.iter_mut() {
+                    if let Some(subsurface) = window.subsurfaces.remove(&id) {
+                        subsurface.destroy();
+                        window.request_redraw(RedrawRequest::NextFrame);
+                        break;
+                    }
+                }
+            }
+            action::Action::SetInputMargin(id, margin) => {
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.set_input_margin(&self.wl_compositor, margin);
+                }
+            }
+            action::Action::SetPresentationHint(id, hint) => {
+                let Some(tearing_control_manager) = &self.tearing_control_manager else {
+                    return;
+                };
+
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.set_presentation_hint(tearing_control_manager, hint);
+                }
+            }
+            action::Action::ConfinePointer(id, confine) => {
+                let Some(window) = self.window_manager.get_mut(id) else {
+                    return;
+                };
+
+                window.release_pointers();
+
+                if !confine {
+                    return;
+                }
+
+                for pointer in window.pointers.keys() {
+                    match self.pointer_constraints.confine_pointer(
+                        window.raw.surface(),
+                        pointer,
+                        None,
+                        Lifetime::Oneshot,
+                        &self.qh,
+                    ) {
+                        Ok(confined_pointer) => {
+                            window
+                                .confined_pointers
+                                .push((pointer.clone(), confined_pointer));
+                        }
+                        Err(error) => {
+                            log::debug!("Pointer of window {id:?} cannot be confined: {error}.");
+                        }
+                    }
+                }
+            }
+            #[cfg(feature = "blur")]
+            action::Action::SetBlur(id, blur) => {
+                let Some(blur_manager) = &self.blur_manager else {
+                    return;
+                };
+
+                if let Some(window) = self.window_manager.get_mut(id) {
+                    window.set_blur(blur_manager, blur);
+                }
+            }
+            action::Action::SetAlpha(id, alpha) => {
+                let Some(window) = self.window_manager.get_mut(id) else {
+                    return;
+                };
+
+                match &self.alpha_modifier {
+                    Some(alpha_modifier) => window.set_alpha(alpha_modifier, alpha),
+                    None => {
+                        if window.state.set_opacity(alpha) {
+                            window.request_redraw(core::window::RedrawRequest::NextFrame);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn create_subsurface(
+        &mut self,
+        parent: core::window::Id,
+        position: core::Point<i32>,
+    ) -> Result<subsurface::Subsurface, subsurface::CreateError> {
+        let Some(subcompositor) = &self.subcompositor else {
+            return Err(subsurface::CreateError::Unsupported);
+        };
+
+        let Some(window) = self.window_manager.get_mut(parent) else {
+            return Err(subsurface::CreateError::WindowNotFound);
+        };
+
+        let (wl_subsurface, wl_surface) =
+            subcompositor.create_subsurface(window.raw.surface().clone(), &self.qh);
+
+        let (embedded, subsurface) =
+            subsurface::Embedded::new(self.display.clone(), wl_subsurface, wl_surface, position);
+
+        let _ = window.subsurfaces.insert(subsurface.id(), embedded);
+
+        // The sub-surface is only mapped once its parent commits.
+        window.request_redraw(RedrawRequest::NextFrame);
+
+        Ok(subsurface)
+    }
+
+    fn set_keyboard_interactivity(
+        &mut self,
+        id: core::window::Id,
+        keyboard_interactivity: core::layer_shell::KeyboardInteractivity,
+    ) {
+        let Some(window) = self.window_manager.get_mut(id) else {
+            return;
+        };
+
+        window.set_keyboard_interactivity(keyboard_interactivity);
+
+        // The surface stops receiving keyboard input right away, but the
+        // compositor is not required to send a `leave` event for it.
+        if matches!(
+            keyboard_interactivity,
+            core::layer_shell::KeyboardInteractivity::None
+        ) && self.keyboard_focuses.remove_window(id)
+        {
+            self.unfocus_window(id);
+        }
+    }
+
+    /// Requests the keyboard focus for a window.
+    ///
+    /// Wayland does not allow clients to take the focus unconditionally, so
+    /// this is only a request and the compositor may deny it.
+    ///
+    /// Layer surfaces without keyboard interactivity can never be focused, so
+    /// their interactivity is raised to `OnDemand` until they lose the focus
+    /// again. If the compositor denies the focus, the surface can still be
+    /// focused by clicking it until then.
+    fn focus_window(&mut self, id: core::window::Id) {
+        let Some(window) = self.window_manager.get_mut(id) else {
+            return;
+        };
+
+        if window.layer_settings.as_ref().is_some_and(|settings| {
+            matches!(
+                settings.keyboard_interactivity,
+                core::layer_shell::KeyboardInteractivity::None
+            )
+        }) {
+            window.raised_interactivity = true;
+            self.set_keyboard_interactivity(id, core::layer_shell::KeyboardInteractivity::OnDemand);
+        }
+
+        self.activate_window(id);
+    }
+
+    /// Creates the graphics compositor shared by every window, compatible
+    /// with the given one.
+    ///
+    /// This blocks the event loop until the compositor is ready, so the
+    /// configures of other surfaces that arrive in the meantime are queued and
+    /// only dispatched afterwards: they always find the compositor created
+    /// by the first one and reuse it, instead of creating their own.
+    fn create_compositor(&mut self, window: &RawWindow) -> Result<(), Error> {
+        let future = self.compositor_future(window);
+        let compositor = self.runtime.block_on(future)?;
+
+        self.store_compositor(compositor)
+    }
+
+    /// Starts creating the graphics compositor without blocking the event
+    /// loop, unless it is already being created.
+    ///
+    /// The creation is polled from the loop itself, since the `Compositor`
+    /// trait does not guarantee that it can be sent to the executor. Once it
+    /// resolves, the windows configured in the meantime are mapped.
+    fn defer_compositor(&mut self, window: &RawWindow) {
+        if self.compositor_source.is_some() {
+            return;
+        }
+
+        let mut future = Box::pin(self.compositor_future(window));
+        let (ping, source) = make_ping().expect("Create ping source");
+        let waker = std::task::Waker::from(Arc::new(PingWaker(ping.clone())));
+
+        let token = self
+            .loop_handle
+            .insert_source(source, move |(), (), state| {
+                let mut context = std::task::Context::from_waker(&waker);
+
+                if let std::task::Poll::Ready(result) = future.as_mut().poll(&mut context) {
+                    state.compositor_created(result);
+                }
+            })
+            .expect("Insert compositor source");
+
+        self.compositor_source = Some(token);
+        ping.ping();
+    }
+
+    /// Stores the compositor created without blocking the event loop and maps
+    /// the windows that were waiting for it.
+    fn compositor_created(
+        &mut self,
+        result: Result<<P::Renderer as compositor::Default>::Compositor, graphics::Error>,
+    ) {
+        if let Some(token) = self.compositor_source.take() {
+            self.loop_handle.remove(token);
+        }
+
+        if let Err(error) = result
+            .map_err(Error::from)
+            .and_then(|compositor| self.store_compositor(compositor))
+        {
+            self.exit(Some(error));
+            return;
+        }
+
+        let configured: Vec<_> = self
+            .in_progress_windows
+            .iter()
+            .filter(|(_, window)| window.configured.is_some())
+            .map(|(surface, _)| surface.clone())
+            .collect();
+
+        for surface in configured {
+            if let Some(window) = self.in_progress_windows.remove(&surface) {
+                self.map_window(window);
+            }
+        }
+    }
+
+    /// Returns the future that creates the graphics compositor, compatible
+    /// with the given window.
+    fn compositor_future(
+        &mut self,
+        window: &RawWindow,
+    ) -> impl Future<
+        Output = Result<<P::Renderer as compositor::Default>::Compositor, graphics::Error>,
+    > + use<P> {
+        let mut graphics_settings: graphics::Settings = self.settings.clone().into();
+
+        if let Some(antialiasing) = self.shell_settings.antialiasing {
+            graphics_settings.antialiasing = antialiasing;
+        }
+
+        // The fonts of the settings and the ones loaded before the compositor
+        // existed are loaded before the compositor is stored, and thus before
+        // the first window is inserted and drawn, so the first frame never
+        // falls back to the default font. Font loading is synchronous, so
+        // nothing else needs to wait for it.
+        let default_fonts: Vec<_> = self
+            .settings
+            .fonts
+            .iter()
+            .cloned()
+            .chain(mem::take(&mut self.pending_fonts))
+            .collect();
+        let window = window.clone();
+
+        async move {
+            let mut compositor =
+                <P::Renderer as compositor::Default>::Compositor::new(graphics_settings, window)
+                    .await;
+            if let Ok(compositor) = &mut compositor {
+                for font in default_fonts {
+                    compositor.load_font(font.clone());
+                }
+            }
+            compositor
+        }
+    }
+
+    /// Stores the created graphics compositor, loading the fonts that were
+    /// loaded while it was being created.
+    fn store_compositor(
+        &mut self,
+        mut compositor: <P::Renderer as compositor::Default>::Compositor,
+    ) -> Result<(), Error> {
+        // The software renderer presents through `wl_shm` buffers, which
+        // garbles the output or fails later on if the compositor does not
+        // support their format.
+        if renderer::Renderer::from(compositor.fetch_information()).is_software()
+            && !self.shm.formats().contains(&SOFTWARE_SHM_FORMAT)
+        {
+            return Err(Error::UnsupportedShmFormat(SOFTWARE_SHM_FORMAT));
+        }
+
+        for font in mem::take(&mut self.pending_fonts) {
+            compositor.load_font(font);
+        }
+
+        log::debug!(
+            "Created the graphics compositor: {:?}.",
+            compositor.fetch_information()
+        );
+
+        self.compositor = Some(compositor);
+
+        Ok(())
+    }
+
+    /// Delivers the files dropped onto a window, both as events of the window
+    /// and to the listeners of drops.
+    fn files_dropped(&mut self, drop: dnd::FilesDropped) {
+        log::debug!("Files dropped onto {:?}: {:?}.", drop.window, drop.paths);
+
+        for path in &drop.paths {
+            self.events.push((
+                drop.window,
+                core::Event::Window(core::window::Event::FileDropped(path.clone())),
+            ));
+        }
+
+        self.file_drop_listeners
+            .retain(|listener| listener.unbounded_send(drop.clone()).is_ok());
+    }
+
+    /// Records an input event of a keyboard, whose serial the selection can be
+    /// set with.
+    fn keyboard_input(&mut self, keyboard: &wl_keyboard::WlKeyboard, serial: u32) {
+        if let Some((seat, _)) = self
+            .keyboards
+            .iter()
+            .find(|(_, candidate)| *candidate == keyboard)
+        {
+            self.last_input = Some((seat.clone(), serial));
+        }
+    }
+
+    /// Offers the text written to the standard clipboard as the selection of
+    /// the seat that received the last input event.
+    ///
+    /// Without a data device or an input event, the clipboard worker offers
+    /// it instead.
+    fn offer_selection(&mut self) {
+        let Some(contents) = self.clipboard.take_unoffered() else {
+            return;
+        };
+
+        let target = self.last_input.as_ref().and_then(|(seat, serial)| {
+            Some((
+                self.data_device_manager.as_ref()?,
+                self.data_devices.get(seat)?,
+                *serial,
+            ))
+        });
+
+        let Some((data_device_manager, data_device, serial)) = target else {
+            // The source of the shell is replaced without being reported as
+            // lost.
+            self.selection_source = None;
+            self.clipboard.hand_over(contents);
+            return;
+        };
+
+        let source = data_device_manager.create_copy_paste_source(&self.qh, selection::MIME_TYPES);
+        source.set_selection(data_device, serial);
+
+        // Dropping the previous source destroys it, so it is not cancelled.
+        self.selection_source = Some(source);
+    }
+
+    /// Pastes the text dropped onto a window into the focusable widget under
+    /// the drop.
+    fn text_dropped(&mut self, id: core::window::Id, position: core::Point, text: String) {
+        log::debug!("Text dropped onto {id:?} at {position:?}.");
+
+        if let Some(window) = self.window_manager.get_mut(id) {
+            window.paste = Some(Paste::Dropped(position, text));
+        }
+    }
+
+    /// Maps a configured window once the compositor exists, resolving its
+    /// open task.
+    fn map_window(&mut self, window: InProgressWindow) {
+        let InProgressWindow {
+            id,
+            raw_window,
+            layer_settings,
+            sender,
+            configured,
+            output_requests,
+        } = window;
+
+        let surface_size = configured.expect("the window should have been configured");
+        let compositor = self
+            .compositor
+            .as_mut()
+            .expect("the compositor should have been created");
+
+        debug::theme_changed(|| {
+            if self.window_manager.is_empty() {
+                let program = self.program_wrapper.as_ref().unwrap().borrow_program();
+                theme::Base::palette(&program.theme(id))
+            } else {
+                None
+            }
+        });
+
+        let program_wrapper = self.program_wrapper.as_mut().unwrap();
+        let window = program_wrapper.with_mut(|fields| {
+            let window = self.window_manager.insert(
+                id,
+                self.conn.clone(),
+                self.qh.clone(),
+                raw_window,
+                layer_settings,
+                surface_size,
+                &self.shell_settings,
+                fields.program,
+                compositor,
+            );
+
+            let logical_size = window.state.logical_size();
+            let _ = fields.user_interfaces.insert(
+                id,
+                build_user_interface(
+                    fields.program,
+                    user_interface::Cache::default(),
+                    &mut window.renderer,
+                    logical_size,
+                    id,
+                ),
+            );
+
+            window
+        });
+
+        window.output_requests = output_requests;
+
+        self.events.push((
+            id,
+            core::Event::Layer(core::layer_shell::Event::Opened {
+                size: window.size(),
+            }),
+        ));
+
+        sender.send(id);
+        window.request_redraw(RedrawRequest::NextFrame);
+    }
+
+    /// Recreates the graphics compositor along with the surfaces and
+    /// renderers of every window.
+    ///
+    /// The user interfaces do not depend on the renderer, so they are kept
+    /// and every window is simply drawn again.
+    fn recreate_compositor(&mut self) -> Result<(), Error> {
+        let Some(raw_window) = self.window_manager.first().map(|window| window.raw.clone()) else {
+            return Ok(());
+        };
+
+        self.compositor = None;
+        self.create_compositor(&raw_window)?;
+
+        let compositor = self
+            .compositor
+            .as_mut()
+            .expect("the compositor should have been created");
+
+        for (_, window) in self.window_manager.iter_mut() {
+            window.recreate_graphics(compositor);
+        }
+
+        self.shell_settings
+            .trace(|| trace::Event::CompositorRecreated);
+
+        Ok(())
+    }
+
+    /// Requests the activation of a window through `xdg_activation_v1`.
+    ///
+    /// Without the serial of a recent input event, most compositors do not
+    /// focus the window and mark it as demanding attention instead (e.g. by
+    /// flashing its taskbar entry). Compositors without the protocol ignore
+    /// the request.
+    fn activate_window(&self, id: core::window::Id) {
+        if let Some(activation) = &self.activation
+            && let Some(window) = self.window_manager.get(id)
+        {
+            activation.request_token(
+                &self.qh,
+                RequestData {
+                    app_id: self.shell_settings.application_id.clone(),
+                    seat_and_serial: None,
+                    surface: Some(window.raw.surface().clone()),
+                },
+            );
+        }
+    }
+
+    /// Synchronizes the text inputs of every seat with the input method
+    /// requested by the window they are focused on.
+    fn sync_text_inputs(&mut self) {
+        for text_input in self.text_inputs.values_mut() {
+            let Some((id, window)) = text_input
+                .focus()
+                .and_then(|surface| self.window_manager.get_mut_alias(surface))
+            else {
+                continue;
+            };
+
+            if let Some(event) = text_input.update(window.ime_request()) {
+                self.events.push((id, core::Event::InputMethod(event)));
+            }
+        }
+    }
+
+    fn unfocus_window(&mut self, id: core::window::Id) {
+        let Some(window) = self.window_manager.get_mut(id) else {
+            return;
+        };
+
+        window
+            .state
+            .update_modifiers(sctk::seat::keyboard::Modifiers::default());
+        self.events.extend(keyboard::unfocused(id));
+        log::debug!("Window {id:?} lost the keyboard focus.");
+        self.shell_settings.trace(|| trace::Event::Unfocused(id));
+
+        if mem::take(&mut window.raised_interactivity) {
+            self.set_keyboard_interactivity(id, core::layer_shell::KeyboardInteractivity::None);
+        }
+    }
+
+    fn exit(&mut self, error: Option<Error>) {
+        self.error = error;
+        self.teardown();
+        self.loop_signal.stop();
+        self.loop_signal.wakeup();
+    }
+
+    /// Destroys every surface and protocol object owned by the shell, so that
+    /// the compositor does not depend on the process exiting to release them.
+    fn teardown(&mut self) {
+        // Dropping the senders resolves any pending `open` tasks.
+        self.in_progress_windows.clear();
+        self.output_groups.clear();
+
+        if let Some(program_wrapper) = self.program_wrapper.as_mut() {
+            program_wrapper.with_user_interfaces_mut(|user_interfaces| user_interfaces.clear());
+        }
+
+        for (_id, window) in self.window_manager.drain() {
+            window.destroy();
+        }
+
+        self.keyboard_focuses.clear();
+        self.touch_focuses.clear();
+        self.compositor = None;
+
+        // Dropping the data source of the selection destroys it.
+        self.selection_source = None;
+
+        // The clipboard worker owns the data and primary selection devices of
+        // the clipboard, and releases them on the shared connection before
+        // it is dropped.
+        self.clipboard = Clipboard::unconnected();
+
+        for (_seat, text_input) in self.text_inputs.drain() {
+            text_input.destroy();
+        }
+
+        // Dropping a data device releases it.
+        self.data_devices.clear();
+
+        for (_seat, keyboard) in self.keyboards.drain() {
+            if keyboard.version() >= 3 {
+                keyboard.release();
+            }
+        }
+
+        for (_seat, themed_pointer) in self.pointers.drain() {
+            let pointer = themed_pointer.pointer();
+
+            if pointer.version() >= 3 {
+                pointer.release();
+            }
+        }
+
+        for (_seat, touch) in self.touch.drain() {
+            if touch.version() >= 3 {
+                touch.release();
+            }
+        }
+
+        if let Some(text_input_manager) = self.text_input_manager.take() {
+            text_input_manager.destroy();
+        }
+
+        if let Some(alpha_modifier) = self.alpha_modifier.take() {
+            alpha_modifier.destroy();
+        }
+
+        if let Some(tearing_control_manager) = self.tearing_control_manager.take() {
+            tearing_control_manager.destroy();
+        }
+
+        if let Err(error) = self.conn.flush() {
+            log::warn!("Error {error:?} when flushing the connection on exit.");
+        }
+    }
+}
+
+/// Updates a user interface with the events of its window, then pastes the
+/// given text at the given position, if any.
+///
+/// The text is pasted into the focusable widget under the position, which
+/// takes the focus first. No click is simulated for that, since text widgets
+/// would count it towards a double click with a preceding one. The commit of
+/// the paste is not one of the events of the window, so it is not reported to
+/// subscriptions.
+fn update_user_interface<Message, Theme, Renderer>(
+    user_interface: &mut UserInterface<'_, Message, Theme, Renderer>,
+    events: &mut Vec<core::Event>,
+    paste: Option<(core::Point, String)>,
+    cursor: core::mouse::Cursor,
+    renderer: &mut Renderer,
+    clipboard: &mut dyn core::Clipboard,
+    messages: &mut Vec<Message>,
+) -> (user_interface::State, Vec<core::event::Status>)
+where
+    Renderer: core::Renderer,
+{
+    let Some((position, contents)) = paste else {
+        return user_interface.update(events, cursor, renderer, clipboard, messages);
+    };
+
+    // Pasting outside of a focusable widget pastes nothing.
+    if !FocusAt::run(user_interface, renderer, position) {
+        return user_interface.update(events, cursor, renderer, clipboard, messages);
+    }
+
+    events.push(core::Event::InputMethod(core::input_method::Event::Commit(
+        contents,
+    )));
+
+    let (state, mut statuses) =
+        user_interface.update(events, cursor, renderer, clipboard, messages);
+
+    let _ = events.pop();
+    statuses.truncate(events.len());
+
+    (state, statuses)
+}
+
+/// An operation that focuses the focusable widget under a position, like a
+/// click on it would, and unfocuses every other one.
+struct FocusAt {
+    position: core::Point,
+    translation: core::Vector,
+    index: usize,
+    target: Option<usize>,
+    focusing: bool,
+}
+
+impl FocusAt {
+    /// Focuses the focusable widget under the position in the user
+    /// interface, returning whether there is one.
+    ///
+    /// The widget is found first, so that the focus does not change if there
+    /// is none.
+    fn run<Message, Theme, Renderer>(
+        user_interface: &mut UserInterface<'_, Message, Theme, Renderer>,
+        renderer: &Renderer,
+        position: core::Point,
+    ) -> bool
+    where
+        Renderer: core::Renderer,
+    {
+        let mut focus_at = Self {
+            position,
+            translation: core::Vector::ZERO,
+            index: 0,
+            target: None,
+            focusing: false,
+        };
+
+        user_interface.operate(renderer, &mut focus_at);
+
+        if focus_at.target.is_none() {
+            return false;
+        }
+
+        focus_at.index = 0;
+        focus_at.focusing = true;
+        user_interface.operate(renderer, &mut focus_at);
+
+        true
+    }
+}
+
+impl core::widget::Operation for FocusAt {
+    fn traverse(&mut self, operate: &mut dyn FnMut(&mut dyn core::widget::Operation)) {
+        let position = self.position;
+
+        self.position = position + mem::take(&mut self.translation);
+        operate(self);
+        self.position = position;
+    }
+
+    fn scrollable(
+        &mut self,
+        _id: Option<&core::widget::Id>,
+        _bounds: core::Rectangle,
+        _content_bounds: core::Rectangle,
+        translation: core::Vector,
+        _state: &mut dyn operation::Scrollable,
+    ) {
+        // The content of a scrollable is laid out unscrolled, so the position
+        // is moved along with it for the traversal of its content.
+        self.translation = translation;
+    }
+
+    fn focusable(
+        &mut self,
+        _id: Option<&core::widget::Id>,
+        bounds: core::Rectangle,
+        state: &mut dyn operation::Focusable,
+    ) {
+        let index = self.index;
+        self.index += 1;
+
+        if !self.focusing {
+            if bounds.contains(self.position) {
+                self.target = Some(index);
+            }
+        } else if self.target == Some(index) {
+            state.focus();
+        } else {
+            state.unfocus();
+        }
+    }
+}
+
+/// Builds a window's [`UserInterface`] for the [`Program`].
+fn build_user_interface<'a, P: Program + 'static>(
+    program: &'a program::Instance<P>,
+    cache: user_interface::Cache,
+    renderer: &mut P::Renderer,
+    size: core::Size,
+    id: core::window::Id,
+) -> UserInterface<'a, P::Message, P::Theme, P::Renderer>
+where
+    P::Theme: theme::Base,
+{
+    let view_span = debug::view(id);
+    let view = program.view(id);
+    view_span.finish();
+
+    let layout_span = debug::layout(id);
+    let user_interface = UserInterface::build(view, size, cache, renderer);
+    layout_span.finish();
+
+    user_interface
+}
+
+sctk::delegate_activation!(@<P: Program + 'static> State<P>);
+sctk::delegate_compositor!(@<P: Program + 'static> State<P>);
+sctk::delegate_data_device!(@<P: Program + 'static> State<P>);
+#[cfg(feature = "dmabuf-feedback")]
+sctk::delegate_dmabuf!(@<P: Program + 'static> State<P>);
+sctk::delegate_keyboard!(@<P: Program + 'static> State<P>);
+sctk::delegate_layer!(@<P: Program + 'static> State<P>);
+sctk::delegate_output!(@<P: Program + 'static> State<P>);
+sctk::delegate_pointer_constraints!(@<P: Program + 'static> State<P>);
+sctk::delegate_pointer!(@<P: Program + 'static> State<P>);
+sctk::delegate_registry!(@<P: Program + 'static> State<P>);
+sctk::delegate_seat!(@<P: Program + 'static> State<P>);
+sctk::delegate_shm!(@<P: Program + 'static> State<P>);
+sctk::delegate_subcompositor!(@<P: Program + 'static> State<P>);
+sctk::delegate_touch!(@<P: Program + 'static> State<P>);
+
+delegate_noop!(@<P: Program + 'static> State<P>: ZwpTextInputManagerV3);
+delegate_noop!(@<P: Program + 'static> State<P>: WpAlphaModifierV1);
+delegate_noop!(@<P: Program + 'static> State<P>: WpAlphaModifierSurfaceV1);
+delegate_noop!(@<P: Program + 'static> State<P>: WpTearingControlManagerV1);
+delegate_noop!(@<P: Program + 'static> State<P>: WpTearingControlV1);
+#[cfg(feature = "blur")]
+delegate_noop!(@<P: Program + 'static> State<P>: OrgKdeKwinBlurManager);
+#[cfg(feature = "blur")]
+delegate_noop!(@<P: Program + 'static> State<P>: OrgKdeKwinBlur);
 
 impl<P: Program + 'static> CompositorHandler for State<P> {
     fn scale_factor_changed(
         &mut self,
         _: &Connection,
         _: &QueueHandle<Self>,
-        _: &wl_surface::WlSurface,
-        _: i32,
+        surface: &wl_surface::WlSurface,
+        new_factor: i32,
     ) {
+        // This is the preferred buffer scale of the surface or, with older
+        // compositors, the maximum scale among the outputs it has entered, so
+        // a window straddling outputs is rendered for the sharpest one.
+        if let Some((id, window)) = self.window_manager.get_mut_alias(surface) {
+            let scale = new_factor.max(1) as u32;
+
+            if window.set_scale(scale) {
+                log::debug!("Buffer scale of window {id:?} changed to {scale}.");
+
+                window.refresh_cursor();
+
+                self.shell_settings
+                    .trace(|| trace::Event::ScaleChanged { id, scale });
+            }
+
+            return;
+        }
+
+        // Themed cursors are loaded at the scale of the cursor surface, so they
+        // are loaded again when it enters an output of another scale.
+        for (_, window) in self.window_manager.iter_mut() {
+            if window
+                .pointers
+                .values()
+                .any(|pointer| pointer.surface() == surface)
+            {
+                window.refresh_cursor();
+            }
+        }
     }
 
     fn transform_changed(
@@ -711,16 +2321,25 @@ impl<P: Program + 'static> CompositorHandler for State<P> {
             return;
         };
         window.redraw_at = core::window::RedrawRequest::Wait;
+        window.frame_arrived();
 
-        let physical_size = window.state.physical_size();
-
-        if physical_size.width == 0 || physical_size.height == 0 {
+        // The frame was requested before the redraws were suspended, so it is
+        // deferred until they are resumed.
+        if window.redraws_suspended() {
+            window.request_redraw(core::window::RedrawRequest::NextFrame);
             return;
         }
 
+        let window::Frame::Present { reconfigure } = window.prepare_frame() else {
+            // The configure that gives the surface a size requests a new
+            // frame.
+            return;
+        };
+
+        let buffer_size = window.state.buffer_size();
         let program_wrapper = self.program_wrapper.as_mut().unwrap();
 
-        if window.viewport_version != window.state.viewport_version() {
+        if reconfigure {
             let logical_size = window.state.logical_size();
 
             program_wrapper.with_user_interfaces_mut(|user_interfaces| {
@@ -733,15 +2352,17 @@ impl<P: Program + 'static> CompositorHandler for State<P> {
 
             compositor.configure_surface(
                 &mut window.surface,
-                physical_size.width,
-                physical_size.height,
+                buffer_size.width,
+                buffer_size.height,
             );
 
             window.viewport_version = window.state.viewport_version();
         }
 
-        let redraw_event =
-            core::Event::Window(core::window::Event::RedrawRequested(Instant::now()));
+        let now = Instant::now();
+        window.redrawn_at(now);
+
+        let redraw_event = core::Event::Window(core::window::Event::RedrawRequested(now));
 
         let cursor = window.state.cursor();
 
@@ -760,7 +2381,7 @@ impl<P: Program + 'static> CompositorHandler for State<P> {
             ui.draw(
                 &mut window.renderer,
                 window.state.theme(),
-                &renderer::Style {
+                &core::renderer::Style {
                     text_color: window.state.text_color(),
                 },
                 cursor,
@@ -790,6 +2411,10 @@ impl<P: Program + 'static> CompositorHandler for State<P> {
 
         window.draw_preedit();
 
+        // NOTE: buffer age is not exposed by the `Compositor` trait. The
+        // `tiny-skia` backend already reads the age of its buffers and only
+        // redraws the damaged regions, while `wgpu` always redraws the whole
+        // surface; threading it through here would require changes upstream.
         let present_span = debug::present(id);
         let present_ok = compositor.present(
             &mut window.renderer,
@@ -800,12 +2425,28 @@ impl<P: Program + 'static> CompositorHandler for State<P> {
         );
         present_span.finish();
 
+        self.shell_settings.trace(|| trace::Event::Presented {
+            id,
+            error: present_ok.as_ref().err().cloned(),
+        });
+
         match present_ok {
             Err(error @ compositor::SurfaceError::OutOfMemory) => {
                 // This is an unrecoverable error.
                 panic!("{error:?}");
             }
 
+            // Presenting keeps failing until the graphics are recreated from
+            // scratch, since the device itself may be gone.
+            Err(compositor::SurfaceError::Lost) => {
+                log::warn!("The surface of window {id:?} was lost; recreating the compositor.");
+
+                if let Err(error) = self.recreate_compositor() {
+                    self.exit(Some(error));
+                    return;
+                }
+            }
+
             Err(error) => {
                 log::error!("Error {error:?} when presenting surface.");
 
@@ -814,26 +2455,50 @@ impl<P: Program + 'static> CompositorHandler for State<P> {
                     window.request_redraw(core::window::RedrawRequest::NextFrame);
                 }
             }
-            _ => {}
+            Ok(()) => {
+                for channel in window.present_requests.drain(..) {
+                    let _ = channel.send(());
+                }
+            }
         }
+
+        self.sync_text_inputs();
     }
 
     fn surface_enter(
         &mut self,
         _: &Connection,
         _: &QueueHandle<Self>,
-        _: &wl_surface::WlSurface,
-        _: &wl_output::WlOutput,
+        surface: &wl_surface::WlSurface,
+        output: &wl_output::WlOutput,
     ) {
+        let Some((_, window)) = self.window_manager.get_mut_alias(surface) else {
+            return;
+        };
+
+        if !window.outputs.contains(output) {
+            window.outputs.push(output.clone());
+        }
+
+        if !window.output_requests.is_empty() {
+            let name = self.output_state.info(output).and_then(|info| info.name);
+
+            for channel in window.output_requests.drain(..) {
+                let _ = channel.send(name.clone());
+            }
+        }
     }
 
     fn surface_leave(
         &mut self,
         _: &Connection,
         _: &QueueHandle<Self>,
-        _: &wl_surface::WlSurface,
-        _: &wl_output::WlOutput,
+        surface: &wl_surface::WlSurface,
+        output: &wl_output::WlOutput,
     ) {
+        if let Some((_, window)) = self.window_manager.get_mut_alias(surface) {
+            window.outputs.retain(|entered| entered != output);
+        }
     }
 }
 
@@ -843,6 +2508,18 @@ impl<P: Program + 'static> OutputHandler for State<P> {
     }
 
     fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, output: wl_output::WlOutput) {
+        log::debug!("Output added: {:?}.", self.output_state.info(&output));
+
+        // Groups whose task has been dropped no longer open new surfaces.
+        let mut output_groups = mem::take(&mut self.output_groups);
+        output_groups.retain(|group| !group.sender.is_closed());
+
+        for group in &mut output_groups {
+            self.open_layer_on_output(group, output.clone());
+        }
+
+        self.output_groups = output_groups;
+
         // TODO: add more info
         self.runtime
             .broadcast(subscription::Event::PlatformSpecific(
@@ -865,6 +2542,27 @@ impl<P: Program + 'static> OutputHandler for State<P> {
         _: &QueueHandle<Self>,
         output: wl_output::WlOutput,
     ) {
+        log::debug!("Output removed: {:?}.", self.output_state.info(&output));
+
+        let closed: Vec<_> = self
+            .output_groups
+            .iter()
+            .flat_map(|group| &group.surfaces)
+            .filter(|(surface_output, _)| *surface_output == output)
+            .map(|(_, id)| *id)
+            .collect();
+
+        for id in closed {
+            self.close_window(id);
+        }
+
+        // Compositors are not required to send a leave event for an output
+        // that goes away, which would leave it as the current output of the
+        // surfaces that were on it.
+        for (_, window) in self.window_manager.iter_mut() {
+            window.outputs.retain(|entered| *entered != output);
+        }
+
         self.runtime
             .broadcast(subscription::Event::PlatformSpecific(
                 subscription::PlatformSpecific::Wayland(subscription::Wayland::OutputRemoved(
@@ -879,6 +2577,10 @@ impl<P: Program + 'static> OutputHandler for State<P> {
 
 impl<P: Program + 'static> LayerShellHandler for State<P> {
     fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, layer_surface: &LayerSurface) {
+        // The compositor may reject a layer surface (e.g. because its output
+        // went away) before its first configure.
+        self.cancel_in_progress_window(layer_surface.wl_surface());
+
         if let Some((id, _)) = self
             .window_manager
             .get_mut_alias(layer_surface.wl_surface())
@@ -887,114 +2589,360 @@ impl<P: Program + 'static> LayerShellHandler for State<P> {
         }
     }
 
+    /// Handles a configure of a layer surface.
+    ///
+    /// `sctk` acknowledges every configure with its `serial` right before
+    /// calling this, so it must not be acknowledged again here. Since buffers
+    /// are only attached when the surface is drawn, in the `frame` callback
+    /// requested below, the first configure is always acknowledged before
+    /// the first buffer is committed.
     fn configure(
         &mut self,
         _: &Connection,
         _: &QueueHandle<Self>,
         layer_surface: &LayerSurface,
         configure: LayerSurfaceConfigure,
-        _: u32,
+        serial: u32,
     ) {
-        let surface_size = core::Size::new(configure.new_size.0, configure.new_size.1);
+        let configured_size = |settings: Option<&core::layer_shell::Settings>| {
+            conversion::layer_shell::configured_size(
+                settings.map_or(core::Size::new(0, 0), |settings| settings.size),
+                configure.new_size,
+            )
+        };
 
-        let Some(InProgressWindow {
-            id,
-            raw_window,
-            sender,
-        }) = self.in_progress_windows.remove(layer_surface.wl_surface())
-        else {
+        let Some(mut window) = self.in_progress_windows.remove(layer_surface.wl_surface()) else {
             if let Some((id, window)) = self
                 .window_manager
                 .get_mut_alias(layer_surface.wl_surface())
             {
-                window.state.resize(surface_size);
-                window.request_redraw(core::window::RedrawRequest::NextFrame);
-                self.events.push((
+                let surface_size = configured_size(window.layer_settings.as_ref());
+
+                log::trace!("Window {id:?} was configured with size {surface_size:?}.");
+
+                self.shell_settings.trace(|| trace::Event::Configured {
                     id,
-                    core::Event::Window(core::window::Event::Resized(
-                        window.state.viewport().logical_size(),
-                    )),
-                ));
+                    size: surface_size,
+                    initial: false,
+                });
+
+                let previous_size = window.state.surface_size();
+
+                // The configure has already been acknowledged, regardless of
+                // whether the size changed.
+                if window.state.resize(surface_size) {
+                    window.notify_shrink(serial, previous_size, surface_size);
+                    window.update_auto_exclusive_zone();
+                    window.update_input_region(&self.wl_compositor);
+                    window.request_redraw(core::window::RedrawRequest::NextFrame);
+                    self.events.push((
+                        id,
+                        core::Event::Window(core::window::Event::Resized(
+                            window.state.viewport().logical_size(),
+                        )),
+                    ));
+                }
             }
 
             return;
         };
 
+        let surface_size = configured_size(window.layer_settings.as_ref());
+        let id = window.id;
+
+        log::debug!("Window {id:?} was mapped with size {surface_size:?}.");
+
+        self.shell_settings.trace(|| trace::Event::Configured {
+            id,
+            size: surface_size,
+            initial: true,
+        });
+
+        window.configured = Some(surface_size);
+
         if self.compositor.is_none() {
-            let graphics_settings = self.settings.clone().into();
-            let default_fonts = self.settings.fonts.clone();
-            let window = raw_window.clone();
-
-            let compositor = self.runtime.block_on(async move {
-                let mut compositor = <P::Renderer as compositor::Default>::Compositor::new(
-                    graphics_settings,
-                    window,
-                )
-                .await;
-                if let Ok(compositor) = &mut compositor {
-                    for font in default_fonts {
-                        compositor.load_font(font.clone());
+            // The window stays pending until the compositor is ready, and a
+            // later configure replaces its size in the meantime.
+            if self.shell_settings.deferred_compositor {
+                self.defer_compositor(&window.raw_window);
+                let _ = self
+                    .in_progress_windows
+                    .insert(layer_surface.wl_surface().clone(), window);
+                return;
+            }
+
+            if let Err(error) = self.create_compositor(&window.raw_window) {
+                self.exit(Some(error));
+                return;
+            }
+        }
+
+        self.map_window(window);
+    }
+}
+
+impl<P: Program + 'static> DataDeviceHandler for State<P> {
+    fn enter(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        data_device: &wl_data_device::WlDataDevice,
+        _: f64,
+        _: f64,
+        _: &wl_surface::WlSurface,
+    ) {
+        let Some(offer) = drag_offer(data_device) else {
+            return;
+        };
+
+        // Only files and text are accepted, so the source shows that anything
+        // else cannot be dropped here.
+        let mime_type = offer.with_mime_types(accepted_mime_type);
+        let accepted = mime_type.is_some();
+
+        offer.accept_mime_type(offer.serial, mime_type);
+
+        if accepted {
+            offer.set_actions(DndAction::Copy, DndAction::Copy);
+        }
+    }
+
+    fn leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_data_device::WlDataDevice) {}
+
+    fn motion(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_data_device::WlDataDevice,
+        _: f64,
+        _: f64,
+    ) {
+    }
+
+    // The selection is read by the clipboard, which has its own data device.
+    fn selection(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_data_device::WlDataDevice,
+    ) {
+    }
+
+    fn drop_performed(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        data_device: &wl_data_device::WlDataDevice,
+    ) {
+        let Some(offer) = drag_offer(data_device) else {
+            return;
+        };
+
+        let mut mime_types = offer.with_mime_types(<[String]>::to_vec);
+
+        let Some((id, window)) = self.window_manager.get_mut_alias(&offer.surface) else {
+            offer.destroy();
+            return;
+        };
+
+        let Some(mime_type) = accepted_mime_type(&mime_types) else {
+            offer.destroy();
+            return;
+        };
+
+        let scale_factor = window.state.scale_factor();
+        let position = core::Point::new(
+            (offer.x / scale_factor) as f32,
+            (offer.y / scale_factor) as f32,
+        );
+
+        let pipe = match offer.receive(mime_type.clone()) {
+            Ok(pipe) => pipe,
+            Err(error) => {
+                log::warn!("Failed to receive the drop onto {id:?}: {error}.");
+                offer.destroy();
+                return;
+            }
+        };
+
+        // The data is read as it arrives, without blocking the loop.
+        let mut data = Vec::new();
+
+        let result = self
+            .loop_handle
+            .insert_source(pipe, move |(), file, state| {
+                let mut buffer = [0; 4096];
+
+                match std::io::Read::read(file, &mut buffer) {
+                    Ok(0) => {}
+                    Ok(read) => {
+                        data.extend_from_slice(&buffer[..read]);
+                        return PostAction::Continue;
                     }
+                    Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {
+                        return PostAction::Continue;
+                    }
+                    Err(error) => {
+                        log::warn!("Failed to read the drop onto {id:?}: {error}.");
+                        offer.destroy();
+                        return PostAction::Remove;
+                    }
+                }
+
+                offer.finish();
+                offer.destroy();
+
+                if mime_type == dnd::URI_LIST {
+                    state.files_dropped(dnd::FilesDropped {
+                        window: id,
+                        position,
+                        paths: dnd::parse_uri_list(&String::from_utf8_lossy(&data)),
+                        mime_types: mem::take(&mut mime_types),
+                    });
+                } else {
+                    state.text_dropped(id, position, dnd::decode_text(&mime_type, &data));
                 }
-                compositor
+
+                PostAction::Remove
             });
-            match compositor {
-                Ok(compositor) => self.compositor = Some(compositor),
+
+        if let Err(error) = result {
+            log::warn!("Failed to read the drop onto {id:?}: {}.", error.error);
+        }
+    }
+}
+
+impl<P: Program + 'static> DataOfferHandler for State<P> {
+    fn source_actions(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &mut DragOffer,
+        _: DndAction,
+    ) {
+    }
+
+    fn selected_action(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &mut DragOffer,
+        _: DndAction,
+    ) {
+    }
+}
+
+// The shell only offers the standard selection through its data devices.
+impl<P: Program + 'static> DataSourceHandler for State<P> {
+    fn accept_mime(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_data_source::WlDataSource,
+        _: Option<String>,
+    ) {
+    }
+
+    // Every MIME type of the selection is sent as UTF-8.
+    fn send_request(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        source: &wl_data_source::WlDataSource,
+        _: String,
+        pipe: WritePipe,
+    ) {
+        let contents = self
+            .selection_source
+            .as_ref()
+            .filter(|selection_source| selection_source.inner() == source)
+            .and_then(|_| self.clipboard.owned());
+
+        // Dropping the pipe closes it, so the client reads nothing.
+        let Some(contents) = contents.map(|contents| contents.as_bytes().to_vec()) else {
+            return;
+        };
+
+        // The contents are written as the pipe drains, without blocking the
+        // loop.
+        let mut written = 0;
+
+        let result = self.loop_handle.insert_source(pipe, move |(), file, _| {
+            // A writable pipe has room for at least a page, so a chunk
+            // never blocks.
+            let end = contents.len().min(written + 4096);
+
+            match std::io::Write::write(file, &contents[written..end]) {
+                Ok(count) => {
+                    written += count;
+
+                    if count > 0 && written < contents.len() {
+                        return PostAction::Continue;
+                    }
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {
+                    return PostAction::Continue;
+                }
                 Err(error) => {
-                    self.exit(Some(error.into()));
-                    return;
+                    log::warn!("Failed to send the selection: {error}.");
                 }
             }
-        }
-        let compositor = self.compositor.as_mut().unwrap();
 
-        debug::theme_changed(|| {
-            if self.window_manager.is_empty() {
-                let program = self.program_wrapper.as_ref().unwrap().borrow_program();
-                theme::Base::palette(&program.theme(id))
-            } else {
-                None
-            }
+            PostAction::Remove
         });
 
-        let program_wrapper = self.program_wrapper.as_mut().unwrap();
-        let window = program_wrapper.with_mut(|fields| {
-            let window = self.window_manager.insert(
-                id,
-                self.conn.clone(),
-                self.qh.clone(),
-                raw_window,
-                surface_size,
-                fields.program,
-                compositor,
-            );
+        if let Err(error) = result {
+            log::warn!("Failed to send the selection: {}.", error.error);
+        }
+    }
 
-            let logical_size = window.state.logical_size();
-            let _ = fields.user_interfaces.insert(
-                id,
-                build_user_interface(
-                    fields.program,
-                    user_interface::Cache::default(),
-                    &mut window.renderer,
-                    logical_size,
-                    id,
-                ),
-            );
+    fn cancelled(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        source: &wl_data_source::WlDataSource,
+    ) {
+        if self
+            .selection_source
+            .as_ref()
+            .is_none_or(|selection_source| selection_source.inner() != source)
+        {
+            return;
+        }
 
-            window
-        });
+        log::debug!("Another client took over the selection.");
 
-        self.events.push((
-            id,
-            core::Event::Layer(layer_shell::Event::Opened {
-                size: window.size(),
-            }),
-        ));
+        // Dropping the source destroys it.
+        self.selection_source = None;
+        self.clipboard.disown();
+
+        self.selection_lost_listeners
+            .retain(|listener| listener.unbounded_send(()).is_ok());
+    }
+
+    fn dnd_dropped(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_data_source::WlDataSource,
+    ) {
+    }
 
-        // TODO: clipboard
+    fn dnd_finished(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_data_source::WlDataSource,
+    ) {
+    }
 
-        let _ = sender.send(id);
-        window.request_redraw(RedrawRequest::NextFrame);
+    fn action(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_data_source::WlDataSource,
+        _: DndAction,
+    ) {
     }
 }
 
@@ -1012,8 +2960,25 @@ impl<P: Program + 'static> SeatHandler for State<P> {
         seat: wl_seat::WlSeat,
         capability: sctk::seat::Capability,
     ) {
+        log::debug!("Seat {:?} gained the {capability} capability.", seat.id());
+
         match capability {
+            // Capabilities may be announced again; the existing devices stay.
+            sctk::seat::Capability::Keyboard if self.keyboards.contains_key(&seat) => {}
+            sctk::seat::Capability::Pointer if self.pointers.contains_key(&seat) => {}
+            sctk::seat::Capability::Touch if self.touch.contains_key(&seat) => {}
             sctk::seat::Capability::Keyboard => {
+                if let Some(text_input_manager) = &self.text_input_manager {
+                    let text_input = text_input_manager.get_text_input(&seat, &self.qh, ());
+                    let _ = self.text_inputs.insert(
+                        seat.clone(),
+                        text_input::TextInput::new(
+                            text_input,
+                            self.shell_settings.capture_input_method,
+                        ),
+                    );
+                }
+
                 if let Ok(keyboard) = self.seat_state.get_keyboard(&self.qh, &seat, None) {
                     let _ = self.keyboards.insert(seat, keyboard);
                 }
@@ -1026,7 +2991,13 @@ impl<P: Program + 'static> SeatHandler for State<P> {
                     self.wl_compositor.create_surface(&self.qh),
                     sctk::seat::pointer::ThemeSpec::System,
                 ) {
-                    let _ = self.pointers.insert(seat, Rc::new(pointer));
+                    let _ = self.pointers.insert(seat.clone(), Rc::new(pointer));
+                }
+
+                // Files are dragged with the pointer.
+                if let Some(data_device_manager) = &self.data_device_manager {
+                    let data_device = data_device_manager.get_data_device(&self.qh, &seat);
+                    let _ = self.data_devices.insert(seat, data_device);
                 }
             }
             sctk::seat::Capability::Touch => {
@@ -1045,15 +3016,74 @@ impl<P: Program + 'static> SeatHandler for State<P> {
         seat: wl_seat::WlSeat,
         capability: sctk::seat::Capability,
     ) {
+        log::debug!("Seat {:?} lost the {capability} capability.", seat.id());
+
         match capability {
             sctk::seat::Capability::Keyboard => {
-                let _ = self.keyboards.remove(&seat);
+                if let Some(text_input) = self.text_inputs.remove(&seat) {
+                    text_input.destroy();
+                }
+
+                if let Some(keyboard) = self.keyboards.remove(&seat) {
+                    self.alt_gr.remove(&keyboard);
+
+                    // The compositor does not send `leave` for a removed
+                    // keyboard.
+                    if let Some(id) = self.keyboard_focuses.leave(&keyboard) {
+                        self.modifier_order.flush(&mut self.events);
+                        self.unfocus_window(id);
+                    }
+
+                    if keyboard.version() >= 3 {
+                        keyboard.release();
+                    }
+                }
             }
             sctk::seat::Capability::Pointer => {
-                let _ = self.pointers.remove(&seat);
+                let _ = self.data_devices.remove(&seat);
+
+                if let Some(themed_pointer) = self.pointers.remove(&seat) {
+                    let pointer = themed_pointer.pointer();
+
+                    // Every window the pointer is over holds onto it, so no
+                    // cursor is set on it once it is released.
+                    for (id, window) in self.window_manager.iter_mut() {
+                        for button in window.release_buttons(pointer) {
+                            self.events.push((
+                                id,
+                                core::Event::Mouse(core::mouse::Event::ButtonReleased(button)),
+                            ));
+                        }
+
+                        if window.remove_pointer(pointer) {
+                            self.events
+                                .push((id, core::Event::Mouse(core::mouse::Event::CursorLeft)));
+                        }
+                    }
+
+                    if pointer.version() >= 3 {
+                        pointer.release();
+                    }
+                }
             }
             sctk::seat::Capability::Touch => {
-                let _ = self.touch.remove(&seat);
+                if let Some(touch) = self.touch.remove(&seat) {
+                    if let Some(touch_focuses) = self.touch_focuses.remove(&touch) {
+                        for (touch_id, (id, position)) in touch_focuses {
+                            self.events.push((
+                                id,
+                                core::Event::Touch(core::touch::Event::FingerLost {
+                                    id: core::touch::Finger(touch_id as u64),
+                                    position,
+                                }),
+                            ));
+                        }
+                    }
+
+                    if touch.version() >= 3 {
+                        touch.release();
+                    }
+                }
             }
             _ => {}
         }
@@ -1069,14 +3099,41 @@ impl<P: Program + 'static> KeyboardHandler for State<P> {
         _: &QueueHandle<Self>,
         keyboard: &wl_keyboard::WlKeyboard,
         surface: &wl_surface::WlSurface,
-        _: u32,
-        _: &[u32],
-        _: &[sctk::seat::keyboard::Keysym],
+        serial: u32,
+        raw: &[u32],
+        keysyms: &[sctk::seat::keyboard::Keysym],
     ) {
-        if let Some((id, _)) = self.window_manager.get_mut_alias(surface) {
-            let _ = self.keyboard_focuses.insert(keyboard.clone(), id);
+        self.keyboard_input(keyboard, serial);
+
+        let Some((id, _)) = self.window_manager.get_mut_alias(surface) else {
+            return;
+        };
+
+        let (unfocused, focused) = self.keyboard_focuses.enter(keyboard.clone(), id);
+
+        if let Some(unfocused) = unfocused {
+            self.modifier_order.flush(&mut self.events);
+            self.unfocus_window(unfocused);
+        }
+
+        if focused {
             self.events
                 .push((id, core::Event::Window(core::window::Event::Focused)));
+            log::debug!("Window {id:?} gained the keyboard focus.");
+            self.shell_settings.trace(|| trace::Event::Focused(id));
+        }
+
+        let Some(window) = self.window_manager.get(id) else {
+            return;
+        };
+
+        // The modifiers of the window are only updated by the `modifiers`
+        // event that follows, so they are derived from the held keys
+        // meanwhile; the window that lost the focus is reset on its own.
+        let modifiers = conversion::keyboard::modifiers(window.state.modifiers());
+
+        for event in conversion::keyboard::entered(keysyms, raw, modifiers) {
+            self.events.push((id, core::Event::Keyboard(event)));
         }
     }
 
@@ -1085,22 +3142,18 @@ impl<P: Program + 'static> KeyboardHandler for State<P> {
         _: &Connection,
         _: &QueueHandle<Self>,
         keyboard: &wl_keyboard::WlKeyboard,
-        surface: &wl_surface::WlSurface,
+        _: &wl_surface::WlSurface,
         _: u32,
     ) {
-        let _ = self.keyboard_focuses.remove(keyboard);
-        if let Some((id, window)) = self.window_manager.get_mut_alias(surface) {
-            window
-                .state
-                .update_modifiers(sctk::seat::keyboard::Modifiers::default());
-            self.events.push((
-                id,
-                core::Event::Keyboard(core::keyboard::Event::ModifiersChanged(
-                    core::keyboard::Modifiers::default(),
-                )),
-            ));
-            self.events
-                .push((id, core::Event::Window(core::window::Event::Unfocused)));
+        // Keys released outside of the surface are never reported.
+        self.alt_gr.remove(keyboard);
+
+        // The focus may have already been dropped when the keyboard
+        // interactivity of the surface was disabled, and the window may still
+        // be focused by the keyboard of another seat.
+        if let Some(id) = self.keyboard_focuses.leave(keyboard) {
+            self.modifier_order.flush(&mut self.events);
+            self.unfocus_window(id);
         }
     }
 
@@ -1109,24 +3162,37 @@ impl<P: Program + 'static> KeyboardHandler for State<P> {
         _: &Connection,
         _: &QueueHandle<Self>,
         keyboard: &wl_keyboard::WlKeyboard,
-        _: u32,
+        serial: u32,
         key_event: sctk::seat::keyboard::KeyEvent,
     ) {
-        if let Some(&id) = self.keyboard_focuses.get(keyboard)
+        self.keyboard_input(keyboard, serial);
+        self.alt_gr
+            .press(keyboard, key_event.keysym, key_event.raw_code);
+
+        // Keys that resolve to no keysym are still reported, as unidentified
+        // keys with their raw code, so they can be bound by physical key.
+        if let Some(id) = self.keyboard_focuses.get(keyboard)
             && let Some(window) = self.window_manager.get_mut(id)
         {
-            let key = conversion::keyboard::key(key_event.keysym);
-            self.events.push((
+            notify_keysym(
+                &mut self.keysym_listeners,
                 id,
-                core::Event::Keyboard(core::keyboard::Event::KeyPressed {
-                    key: key.clone(),
-                    modified_key: key.clone(), // TODO: actually get modified key
-                    physical_key: conversion::keyboard::code(key_event.keysym, key_event.raw_code),
-                    location: conversion::keyboard::location(key_event.keysym),
-                    modifiers: conversion::keyboard::modifiers(window.state.modifiers()),
-                    text: key_event.utf8.map(SmolStr::new),
-                }),
-            ));
+                &key_event,
+                true,
+                conversion::keyboard::modifiers(window.state.modifiers()),
+                self.alt_gr.is_held(keyboard),
+            );
+
+            self.modifier_order.key(
+                id,
+                conversion::keyboard::pressed(
+                    key_event.keysym,
+                    key_event.raw_code,
+                    key_event.utf8.map(SmolStr::new),
+                    conversion::keyboard::modifiers(window.state.modifiers()),
+                ),
+                &mut self.events,
+            );
         }
     }
 
@@ -1138,20 +3204,32 @@ impl<P: Program + 'static> KeyboardHandler for State<P> {
         _: u32,
         key_event: sctk::seat::keyboard::KeyEvent,
     ) {
-        if let Some(&id) = self.keyboard_focuses.get(keyboard)
+        self.alt_gr.release(keyboard, key_event.raw_code);
+
+        if let Some(id) = self.keyboard_focuses.get(keyboard)
             && let Some(window) = self.window_manager.get_mut(id)
         {
+            notify_keysym(
+                &mut self.keysym_listeners,
+                id,
+                &key_event,
+                false,
+                conversion::keyboard::modifiers(window.state.modifiers()),
+                self.alt_gr.is_held(keyboard),
+            );
+
             let key = conversion::keyboard::key(key_event.keysym);
-            self.events.push((
+            self.modifier_order.key(
                 id,
-                core::Event::Keyboard(core::keyboard::Event::KeyReleased {
+                core::keyboard::Event::KeyReleased {
                     key: key.clone(),
                     modified_key: key.clone(), // TODO: actually get modified key
                     physical_key: conversion::keyboard::code(key_event.keysym, key_event.raw_code),
                     location: conversion::keyboard::location(key_event.keysym),
                     modifiers: conversion::keyboard::modifiers(window.state.modifiers()),
-                }),
-            ));
+                },
+                &mut self.events,
+            );
         }
     }
 
@@ -1164,17 +3242,140 @@ impl<P: Program + 'static> KeyboardHandler for State<P> {
         modifiers: sctk::seat::keyboard::Modifiers,
         _: u32,
     ) {
-        if let Some(&id) = self.keyboard_focuses.get(keyboard)
+        if let Some(id) = self.keyboard_focuses.get(keyboard)
             && let Some(window) = self.window_manager.get_mut(id)
         {
             window.state.update_modifiers(modifiers);
-            self.events.push((
+            self.modifier_order.modifiers(
                 id,
-                core::Event::Keyboard(core::keyboard::Event::ModifiersChanged(
-                    conversion::keyboard::modifiers(modifiers),
-                )),
-            ));
+                conversion::keyboard::modifiers(modifiers),
+                &mut self.events,
+            );
+        }
+    }
+}
+
+/// Sends a key event to the listeners of raw keysyms, dropping the ones that
+/// are gone.
+fn notify_keysym(
+    listeners: &mut Vec<mpsc::UnboundedSender<keysym::Event>>,
+    window: core::window::Id,
+    key_event: &sctk::seat::keyboard::KeyEvent,
+    is_pressed: bool,
+    modifiers: core::keyboard::Modifiers,
+    alt_gr: bool,
+) {
+    if listeners.is_empty() {
+        return;
+    }
+
+    let event = keysym::Event {
+        window,
+        keysym: key_event.keysym.raw(),
+        raw_code: key_event.raw_code,
+        is_pressed,
+        text: key_event.utf8.clone(),
+        modifiers,
+        alt_gr,
+    };
+
+    listeners.retain(|listener| listener.unbounded_send(event.clone()).is_ok());
+}
+
+/// Returns the offer of the drag that is over the surfaces of a data device.
+fn drag_offer(data_device: &wl_data_device::WlDataDevice) -> Option<DragOffer> {
+    data_device.data::<DataDeviceData>()?.drag_offer()
+}
+
+/// Returns the MIME type to read a drag with, preferring files over text.
+fn accepted_mime_type(mime_types: &[String]) -> Option<String> {
+    if mime_types
+        .iter()
+        .any(|mime_type| mime_type == dnd::URI_LIST)
+    {
+        return Some(dnd::URI_LIST.to_owned());
+    }
+
+    dnd::preferred_text(mime_types.iter().map(String::as_str)).map(str::to_owned)
+}
+
+/// Sends the press or release of a pointer button to its listeners, dropping
+/// the ones that are gone.
+fn notify_pointer_button(
+    listeners: &mut Vec<mpsc::UnboundedSender<pointer::Event>>,
+    window: core::window::Id,
+    button: u32,
+    is_pressed: bool,
+    position: core::Point,
+    scale_factor: f64,
+    time: u32,
+) {
+    if listeners.is_empty() {
+        return;
+    }
+
+    let event = pointer::Event {
+        window,
+        button: conversion::mouse::button(button),
+        is_pressed,
+        position: core::Point::new(
+            position.x / scale_factor as f32,
+            position.y / scale_factor as f32,
+        ),
+        time,
+    };
+
+    listeners.retain(|listener| listener.unbounded_send(event).is_ok());
+}
+
+impl<P: Program + 'static> PointerConstraintsHandler for State<P> {
+    fn confined(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &ZwpConfinedPointerV1,
+        _: &wl_surface::WlSurface,
+        _: &wl_pointer::WlPointer,
+    ) {
+    }
+
+    fn unconfined(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        confined_pointer: &ZwpConfinedPointerV1,
+        surface: &wl_surface::WlSurface,
+        _: &wl_pointer::WlPointer,
+    ) {
+        // A one-shot confinement ends for good, like when the window loses
+        // focus, so it is destroyed right away.
+        if let Some((_, window)) = self.window_manager.get_mut_alias(surface) {
+            window
+                .confined_pointers
+                .retain(|(_, confined)| confined != confined_pointer);
         }
+
+        confined_pointer.destroy();
+    }
+
+    fn locked(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &ZwpLockedPointerV1,
+        _: &wl_surface::WlSurface,
+        _: &wl_pointer::WlPointer,
+    ) {
+    }
+
+    fn unlocked(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &ZwpLockedPointerV1,
+        _: &wl_surface::WlSurface,
+        _: &wl_pointer::WlPointer,
+    ) {
     }
 }
 
@@ -1207,52 +3408,116 @@ impl<P: Program + 'static> PointerHandler for State<P> {
                         }
 
                         window.state.update_cursor(Some(position));
-                        self.events
-                            .push((id, core::Event::Mouse(core::mouse::Event::CursorEntered)));
+                        window.show_cursor();
+                        self.events.extend(
+                            conversion::mouse::entered(position, window.state.scale_factor())
+                                .map(|event| (id, core::Event::Mouse(event))),
+                        );
                     }
                     PEK::Motion { .. } => {
-                        let scale_factor = window.state.scale_factor();
                         window.state.update_cursor(Some(position));
+                        window.show_cursor();
                         self.events.push((
                             id,
-                            core::Event::Mouse(core::mouse::Event::CursorMoved {
-                                position: core::Point::new(
-                                    position.x / (scale_factor as f32),
-                                    position.y / (scale_factor as f32),
-                                ),
-                            }),
+                            core::Event::Mouse(conversion::mouse::cursor_moved(
+                                position,
+                                window.state.scale_factor(),
+                            )),
+                        ));
+                    }
+                    PEK::Press {
+                        button,
+                        time,
+                        serial,
+                    } => {
+                        if let Some(data) = pointer.data::<PointerData>() {
+                            self.last_input = Some((data.seat().clone(), *serial));
+                        }
+
+                        notify_pointer_button(
+                            &mut self.pointer_listeners,
+                            id,
+                            *button,
+                            true,
+                            position,
+                            window.state.scale_factor(),
+                            *time,
+                        );
+                        window.held_buttons.push((pointer.clone(), *button));
+                        self.events.push((
+                            id,
+                            core::Event::Mouse(core::mouse::Event::ButtonPressed(
+                                conversion::mouse::button(*button),
+                            )),
+                        ));
+
+                        // Middle-click pastes the primary selection into text
+                        // widgets, like native Wayland text entries. The
+                        // selection is read once the events are processed.
+                        if *button == sctk::seat::pointer::BTN_MIDDLE
+                            && window.mouse_interaction == core::mouse::Interaction::Text
+                        {
+                            window.paste = window.state.cursor().position().map(Paste::Primary);
+                        }
+                    }
+                    PEK::Release { button, time, .. } => {
+                        notify_pointer_button(
+                            &mut self.pointer_listeners,
+                            id,
+                            *button,
+                            false,
+                            position,
+                            window.state.scale_factor(),
+                            *time,
+                        );
+                        window
+                            .held_buttons
+                            .retain(|held| held.0 != *pointer || held.1 != *button);
+
+                        // The drag ends once its last button is released.
+                        if !window.held_buttons.iter().any(|held| held.0 == *pointer) {
+                            window.release_pointer(pointer);
+                        }
+
+                        self.events.push((
+                            id,
+                            core::Event::Mouse(core::mouse::Event::ButtonReleased(
+                                conversion::mouse::button(*button),
+                            )),
                         ));
                     }
-                    PEK::Press { button, .. } => self.events.push((
-                        id,
-                        core::Event::Mouse(core::mouse::Event::ButtonPressed(
-                            conversion::mouse::button(*button),
-                        )),
-                    )),
-                    PEK::Release { button, .. } => self.events.push((
-                        id,
-                        core::Event::Mouse(core::mouse::Event::ButtonReleased(
-                            conversion::mouse::button(*button),
-                        )),
-                    )),
                     PEK::Axis {
                         horizontal,
                         vertical,
                         ..
-                    } => self.events.push((
-                        id,
-                        core::Event::Mouse(core::mouse::Event::WheelScrolled {
-                            delta: core::mouse::ScrollDelta::Pixels {
-                                x: horizontal.absolute as f32,
-                                y: vertical.absolute as f32,
-                            },
-                        }),
-                    )),
+                    } => self.events.extend(
+                        conversion::mouse::scrolled(
+                            horizontal.absolute,
+                            vertical.absolute,
+                            position,
+                            window.state.scale_factor(),
+                        )
+                        .map(|event| (id, core::Event::Mouse(event))),
+                    ),
+                    // The compositor keeps sending the events of a pointer to
+                    // the surface where a button was pressed until it is
+                    // released, even outside of it. A pointer only leaves
+                    // with a button held when that grab is broken (e.g. by a
+                    // shortcut of the compositor), in which case the release
+                    // is never sent: it is synthesized at the last position
+                    // of the cursor, so drags end instead of getting stuck.
                     PEK::Leave { .. } => {
-                        let _ = window.pointers.remove(pointer);
-                        window.state.update_cursor(None);
-                        self.events
-                            .push((id, core::Event::Mouse(core::mouse::Event::CursorLeft)));
+                        for button in window.release_buttons(pointer) {
+                            self.events.push((
+                                id,
+                                core::Event::Mouse(core::mouse::Event::ButtonReleased(button)),
+                            ));
+                        }
+
+                        if window.remove_pointer(pointer) {
+                            self.events
+                                .push((id, core::Event::Mouse(core::mouse::Event::CursorLeft)));
+                        }
                     }
                 }
             }
@@ -1288,6 +3553,17 @@ impl<P: Program + 'static> TouchHandler for State<P> {
                     position,
                 }),
             ));
+
+            // The gestures are turned into mouse events, which are in the
+            // coordinates of the user interface.
+            if let Some(gestures) = &mut self.gestures {
+                gestures.press(
+                    id,
+                    touch_id,
+                    conversion::mouse::logical(position, window.state.scale_factor()),
+                    Instant::now(),
+                );
+            }
         }
     }
 
@@ -1302,6 +3578,10 @@ impl<P: Program + 'static> TouchHandler for State<P> {
     ) {
         if let Some(touch_focuses) = self.touch_focuses.get_mut(touch)
             && let Some((id, position)) = touch_focuses.remove(&touch_id)
+            && !self
+                .gestures
+                .as_mut()
+                .is_some_and(|gestures| gestures.lift(touch_id, &mut self.events))
         {
             self.events.push((
                 id,
@@ -1328,6 +3608,16 @@ impl<P: Program + 'static> TouchHandler for State<P> {
         {
             *position = core::Point::new(new_position.0 as f32, new_position.1 as f32);
             window.state.update_cursor(Some(*position));
+
+            let logical_position =
+                conversion::mouse::logical(*position, window.state.scale_factor());
+
+            if self.gestures.as_mut().is_some_and(|gestures| {
+                gestures.motion(touch_id, logical_position, &mut self.events)
+            }) {
+                return;
+            }
+
             self.events.push((
                 *id,
                 core::Event::Touch(core::touch::Event::FingerMoved {
@@ -1360,6 +3650,10 @@ impl<P: Program + 'static> TouchHandler for State<P> {
     }
 
     fn cancel(&mut self, _: &Connection, _: &QueueHandle<Self>, touch: &wl_touch::WlTouch) {
+        if let Some(gestures) = &mut self.gestures {
+            gestures.cancel(&mut self.events);
+        }
+
         if let Some(touch_focuses) = self.touch_focuses.remove(touch) {
             for (touch_id, (id, position)) in touch_focuses {
                 self.events.push((
@@ -1374,6 +3668,57 @@ impl<P: Program + 'static> TouchHandler for State<P> {
     }
 }
 
+impl<P: Program + 'static> ActivationHandler for State<P> {
+    type RequestData = RequestData;
+
+    fn new_token(&mut self, token: String, data: &Self::RequestData) {
+        if let Some(activation) = &self.activation
+            && let Some(surface) = &data.surface
+        {
+            activation.activate::<Self>(surface, token);
+        }
+    }
+}
+
+#[cfg(feature = "dmabuf-feedback")]
+impl<P: Program + 'static> DmabufHandler for State<P> {
+    fn dmabuf_state(&mut self) -> &mut DmabufState {
+        &mut self.dmabuf
+    }
+
+    fn dmabuf_feedback(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        proxy: &ZwpLinuxDmabufFeedbackV1,
+        feedback: DmabufFeedback,
+    ) {
+        let feedback = dmabuf::Feedback::from(&feedback);
+
+        if let Some((_, window)) = self
+            .window_manager
+            .iter_mut()
+            .find(|(_, window)| window.has_dmabuf_feedback(proxy))
+        {
+            window.notify_dmabuf_feedback(feedback);
+        }
+    }
+
+    // The shell never creates dmabuf buffers itself.
+    fn created(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &ZwpLinuxBufferParamsV1,
+        _: wl_buffer::WlBuffer,
+    ) {
+    }
+
+    fn failed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &ZwpLinuxBufferParamsV1) {}
+
+    fn released(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_buffer::WlBuffer) {}
+}
+
 impl<P: Program + 'static> ShmHandler for State<P> {
     fn shm_state(&mut self) -> &mut Shm {
         &mut self.shm
@@ -1387,3 +3732,205 @@ impl<P: Program + 'static> ProvidesRegistryState for State<P> {
 
     registry_handlers![OutputState, SeatState];
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn idle_loop_timer_fires_once() {
+        let mut event_loop = EventLoop::<usize>::try_new().unwrap();
+
+        let _ = event_loop
+            .handle()
+            .insert_source(Timer::immediate(), |_, _, fires| {
+                *fires += 1;
+                timeout_action(None)
+            })
+            .unwrap();
+
+        let mut fires = 0;
+        for _ in 0..3 {
+            event_loop
+                .dispatch(Some(Duration::from_millis(10)), &mut fires)
+                .unwrap();
+        }
+
+        assert_eq!(fires, 1);
+    }
+
+    #[test]
+    fn only_affected_windows_are_redrawn() {
+        let [clicked, restyled, idle] = [
+            core::window::Id::unique(),
+            core::window::Id::unique(),
+            core::window::Id::unique(),
+        ];
+        let synchronized = [(clicked, false), (restyled, true), (idle, false)];
+        let affected = FxHashSet::from_iter([clicked]);
+
+        assert_eq!(
+            redrawn_windows(true, false, &affected, synchronized),
+            [clicked, restyled]
+        );
+
+        // Messages that do not come from a window redraw all of them.
+        assert_eq!(
+            redrawn_windows(true, true, &affected, synchronized),
+            [clicked, restyled, idle]
+        );
+
+        // Every window is redrawn by default.
+        assert_eq!(
+            redrawn_windows(false, false, &affected, synchronized),
+            [clicked, restyled, idle]
+        );
+    }
+
+    #[test]
+    fn middle_click_pastes_into_the_clicked_field() {
+        use iced_widget::{column, text_input};
+
+        #[derive(Debug, Clone, PartialEq)]
+        enum Message {
+            First(String),
+            Second(String),
+        }
+
+        let view: core::Element<'_, Message, iced_widget::Theme, ()> = column![
+            text_input("", "").on_input(Message::First),
+            text_input("", "").on_input(Message::Second),
+        ]
+        .into();
+
+        let mut renderer = ();
+        let mut user_interface = UserInterface::build(
+            view,
+            core::Size::new(200.0, 200.0),
+            user_interface::Cache::default(),
+            &mut renderer,
+        );
+        let mut messages = Vec::new();
+        let first = core::mouse::Cursor::Available(core::Point::new(10.0, 5.0));
+        let second = core::mouse::Cursor::Available(core::Point::new(10.0, 50.0));
+
+        // The first field is focused.
+        let _ = update_user_interface(
+            &mut user_interface,
+            &mut vec![core::Event::Mouse(core::mouse::Event::ButtonPressed(
+                core::mouse::Button::Left,
+            ))],
+            None,
+            first,
+            &mut renderer,
+            &mut core::clipboard::Null,
+            &mut messages,
+        );
+
+        // The second one is middle-clicked.
+        let mut events = vec![core::Event::Mouse(core::mouse::Event::ButtonPressed(
+            core::mouse::Button::Middle,
+        ))];
+        let (_, statuses) = update_user_interface(
+            &mut user_interface,
+            &mut events,
+            second
+                .position()
+                .map(|position| (position, String::from("pasted"))),
+            second,
+            &mut renderer,
+            &mut core::clipboard::Null,
+            &mut messages,
+        );
+
+        assert_eq!(messages, [Message::Second(String::from("pasted"))]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(statuses.len(), 1);
+    }
+
+    #[test]
+    fn middle_click_right_after_a_click_keeps_the_text() {
+        use iced_widget::text_input;
+
+        let view: core::Element<'_, String, iced_widget::Theme, ()> =
+            text_input("", "word").on_input(String::from).into();
+
+        let mut renderer = ();
+        let mut user_interface = UserInterface::build(
+            view,
+            core::Size::new(200.0, 200.0),
+            user_interface::Cache::default(),
+            &mut renderer,
+        );
+        let mut messages = Vec::new();
+        let position = core::Point::new(10.0, 5.0);
+        let cursor = core::mouse::Cursor::Available(position);
+
+        // A click followed by another one would select the word.
+        let _ = update_user_interface(
+            &mut user_interface,
+            &mut vec![
+                core::Event::Mouse(core::mouse::Event::ButtonPressed(core::mouse::Button::Left)),
+                core::Event::Mouse(core::mouse::Event::ButtonReleased(
+                    core::mouse::Button::Left,
+                )),
+            ],
+            None,
+            cursor,
+            &mut renderer,
+            &mut core::clipboard::Null,
+            &mut messages,
+        );
+
+        let _ = update_user_interface(
+            &mut user_interface,
+            &mut vec![core::Event::Mouse(core::mouse::Event::ButtonPressed(
+                core::mouse::Button::Middle,
+            ))],
+            Some((position, String::from(" pasted"))),
+            cursor,
+            &mut renderer,
+            &mut core::clipboard::Null,
+            &mut messages,
+        );
+
+        assert_eq!(messages, [String::from("word pasted")]);
+    }
+
+    #[test]
+    fn closing_before_configure_resolves_the_open_task() {
+        let in_progress = |sender| (core::window::Id::unique(), sender);
+
+        let (sender, mut receiver) = oneshot::channel();
+        let (runtime_sender, mut runtime_receiver) = oneshot::channel::<core::window::Id>();
+
+        let mut in_progress_windows = FxHashMap::default();
+        let _ = in_progress_windows.insert("closed", in_progress(OpenSender::Shell(sender)));
+        let _ =
+            in_progress_windows.insert("runtime", in_progress(OpenSender::Runtime(runtime_sender)));
+        let _ = in_progress_windows.insert(
+            "configured",
+            in_progress(OpenSender::Runtime(oneshot::channel().0)),
+        );
+
+        assert!(cancel_in_progress(&mut in_progress_windows, &"closed").is_some());
+        assert_eq!(
+            receiver.try_recv(),
+            Ok(Some(Err(layer_shell::OpenError::Closed)))
+        );
+
+        // The runtime task ends without producing a window.
+        assert!(cancel_in_progress(&mut in_progress_windows, &"runtime").is_some());
+        assert!(runtime_receiver.try_recv().is_err());
+
+        // Closing a surface again or one that is not in progress does nothing.
+        assert_eq!(
+            cancel_in_progress(&mut in_progress_windows, &"closed"),
+            None
+        );
+        assert_eq!(in_progress_windows.len(), 1);
+        assert!(in_progress_windows.contains_key("configured"));
+    }
+}
@@ -0,0 +1,144 @@
+//! Listen to the buttons of the pointer along with their timestamps.
+//!
+//! The mouse events of the user interface carry no timestamp, so widgets
+//! count clicks with the instant at which they process each press. When the
+//! application is busy, presses that are queued together are processed
+//! together, which turns two slow clicks into a double click, while a slow
+//! frame between two fast clicks splits them. The events of this module carry
+//! the timestamps of the compositor, which are taken when the buttons are
+//! actually pressed.
+use std::time::Duration;
+
+use crate::{
+    action,
+    core::{mouse, window, Point},
+    runtime::Task,
+};
+
+/// A button of the pointer that was pressed or released.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Event {
+    /// The [`window::Id`] of the surface under the pointer.
+    pub window: window::Id,
+
+    /// The [`mouse::Button`] that was pressed or released.
+    pub button: mouse::Button,
+
+    /// Whether the button was pressed; otherwise, it was released.
+    pub is_pressed: bool,
+
+    /// The position of the pointer, in logical coordinates.
+    pub position: Point,
+
+    /// The timestamp of the compositor, in milliseconds.
+    ///
+    /// Its base is undefined, so it is only meaningful when compared with the
+    /// timestamps of other events. It wraps around after about 49 days.
+    pub time: u32,
+}
+
+/// Listens to the buttons of every pointer over a surface of the
+/// application.
+///
+/// The mouse events of the user interface are produced as usual and in the
+/// same order, so this can be used alongside widgets: a widget that needs
+/// accurate click counts (e.g. a text editor selecting words on double click)
+/// should take them from a [`Clicks`] fed with these events by the
+/// application, instead of from its own [`mouse::Click`].
+pub fn listen() -> Task<Event> {
+    action::stream(action::Action::ListenPointerButtons)
+}
+
+/// Counts the consecutive clicks of the pointer from the timestamps of its
+/// [`Event`]s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clicks {
+    interval: Duration,
+    last: Option<(Event, usize)>,
+}
+
+impl Clicks {
+    /// The default maximum interval between two presses of the same click.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(300);
+
+    /// How far the pointer may move between two presses of the same click,
+    /// in logical pixels.
+    const SLOP: f32 = 6.0;
+
+    /// Creates a [`Clicks`] counter with the given maximum interval between
+    /// two presses of the same click (e.g. the double-click time of the
+    /// desktop).
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: None,
+        }
+    }
+
+    /// Records the [`Event`], returning the number of consecutive clicks if
+    /// it is the press of a button: `1` for a single click, `2` for a double
+    /// click, and so on.
+    pub fn record(&mut self, event: &Event) -> Option<usize> {
+        if !event.is_pressed {
+            return None;
+        }
+
+        let count = match &self.last {
+            Some((last, count))
+                if last.window == event.window
+                    && last.button == event.button
+                    && last.position.distance(event.position) < Self::SLOP
+                    && u128::from(event.time.wrapping_sub(last.time))
+                        <= self.interval.as_millis() =>
+            {
+                count + 1
+            }
+            _ => 1,
+        };
+
+        self.last = Some((*event, count));
+
+        Some(count)
+    }
+}
+
+impl Default for Clicks {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_INTERVAL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clicks_are_counted_with_the_compositor_time() {
+        let window = window::Id::unique();
+        let press = |time| Event {
+            window,
+            button: mouse::Button::Left,
+            is_pressed: true,
+            position: Point::new(10.0, 10.0),
+            time,
+        };
+
+        let mut clicks = Clicks::default();
+
+        // Presses that are processed late still count as a double click, and
+        // the timestamps may wrap around.
+        assert_eq!(clicks.record(&press(u32::MAX - 100)), Some(1));
+        assert_eq!(
+            clicks.record(&Event {
+                is_pressed: false,
+                ..press(u32::MAX - 50)
+            }),
+            None
+        );
+        assert_eq!(clicks.record(&press(100)), Some(2));
+        assert_eq!(clicks.record(&press(350)), Some(3));
+
+        // A slow press starts a new click.
+        assert_eq!(clicks.record(&press(1000)), Some(1));
+    }
+}
@@ -0,0 +1,46 @@
+//! Query the renderer of the shell.
+use crate::{action, graphics::compositor, runtime::Task};
+
+/// The renderer used to draw the surfaces of the application.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Renderer {
+    /// The graphics backend, like `Vulkan`, or `software` for the software
+    /// renderer.
+    pub backend: String,
+    /// The graphics adapter, like the name of the GPU.
+    pub adapter: String,
+}
+
+impl Renderer {
+    /// Returns whether the surfaces are rendered on the CPU.
+    pub fn is_software(&self) -> bool {
+        self.backend == SOFTWARE
+    }
+}
+
+const SOFTWARE: &str = "software";
+
+impl From<compositor::Information> for Renderer {
+    fn from(information: compositor::Information) -> Self {
+        let backend = if information.backend == "tiny-skia" {
+            SOFTWARE.to_owned()
+        } else {
+            information.backend
+        };
+
+        Self {
+            backend,
+            adapter: information.adapter,
+        }
+    }
+}
+
+/// Fetches the active [`Renderer`], which is useful for diagnostics and bug
+/// reports.
+///
+/// Unlike the system information, this is cheap and available regardless of
+/// the backend. The renderer is created along with the first surface, so this
+/// produces `None` until a surface has been opened.
+pub fn fetch() -> Task<Option<Renderer>> {
+    action::oneshot(action::Action::GetRenderer)
+}
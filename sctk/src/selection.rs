@@ -0,0 +1,21 @@
+//! Listen to the standard selection of the shell being taken over.
+//!
+//! The text written to the standard clipboard is offered by the shell itself,
+//! as the selection of the seat that received the last input, until another
+//! client sets a selection of its own. Then the contents are forgotten and
+//! [`listen_lost`] produces a value, e.g. to stop showing that something was
+//! copied.
+//!
+//! Without a data device or any input yet, the clipboard worker offers the
+//! text instead, and losing it is not reported.
+use crate::{action, runtime::Task};
+
+/// The MIME types the shell offers its selection with, which are the UTF-8
+/// ones of [`dnd::TEXT`](crate::dnd::TEXT).
+pub const MIME_TYPES: [&str; 3] = ["text/plain;charset=utf-8", "UTF8_STRING", "text/plain"];
+
+/// Listens to another client taking over the standard selection offered by
+/// the shell.
+pub fn listen_lost() -> Task<()> {
+    action::stream(action::Action::ListenSelectionLost)
+}
@@ -0,0 +1,131 @@
+use crate::{
+    graphics::Antialiasing,
+    touch,
+    trace::{self, Trace},
+};
+
+/// The settings of the shell.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// Whether the logical size of windows is snapped to whole pixels.
+    ///
+    /// With fractional scale factors, the logical size of a window may not
+    /// be a whole number, which blurs the edges of widgets laid out against
+    /// its right or bottom sides. Snapping rounds the logical size down and
+    /// renders it to the nearest whole number of device pixels, keeping
+    /// those edges crisp at the cost of leaving up to one logical pixel
+    /// along those sides uncovered by the user interface.
+    ///
+    /// By default, windows are scaled exactly.
+    pub snap_to_pixels: bool,
+
+    /// The anti-aliasing strategy of the renderer, overriding the
+    /// `antialiasing` flag of the application settings.
+    ///
+    /// That flag can only enable [`Antialiasing::MSAAx4`]; this allows
+    /// choosing another sample count (e.g. [`Antialiasing::MSAAx8`] for a
+    /// user interface with lots of rounded corners) or disabling it with
+    /// `Some(None)` regardless of the flag, which is useful on low-power
+    /// devices.
+    ///
+    /// The renderer is created along with the first surface, so this cannot
+    /// be changed afterwards.
+    pub antialiasing: Option<Option<Antialiasing>>,
+
+    /// Whether every `CursorMoved` event is reported to the user interface.
+    ///
+    /// By default, consecutive cursor movements between two updates are
+    /// coalesced into the latest one, which saves work when the pointer
+    /// moves faster than the frame rate. Drawing applications that need
+    /// every sample can disable this.
+    pub every_cursor_move: bool,
+
+    /// Whether only the windows affected by an update are redrawn after it.
+    ///
+    /// A window is affected when it received events or published messages,
+    /// or when its title, scale factor or theme changed. Messages of tasks
+    /// and subscriptions still redraw every window, since they may concern
+    /// any of them.
+    ///
+    /// This saves work for daemons with many surfaces, but a message
+    /// published by one window that changes the view of another one is only
+    /// shown there once it is redrawn for another reason.
+    ///
+    /// By default, every window is redrawn after an update.
+    pub redraw_affected_windows_only: bool,
+
+    /// Whether the graphics compositor is created without blocking the event
+    /// loop.
+    ///
+    /// The compositor is created along with the first surface. By default,
+    /// the loop waits for it, so the first frame is presented as soon as
+    /// possible but no other events are dispatched in the meantime, which
+    /// stalls input on slow GPUs. When set, surfaces that are configured in
+    /// the meantime stay pending and are only shown once it is ready, which
+    /// delays the first frame but keeps input flowing.
+    ///
+    /// Backends that do their work synchronously when first polled still
+    /// block the loop for that work.
+    pub deferred_compositor: bool,
+
+    /// The thresholds of the touch gestures, if they are recognized.
+    ///
+    /// When set, holding a finger in place selects the word under it in text
+    /// widgets, and dragging it afterwards extends the selection, like a
+    /// mouse double click that is held down. Taps and drags are handled by
+    /// the widgets either way.
+    ///
+    /// By default, touch gestures are not recognized.
+    pub touch_gestures: Option<touch::Thresholds>,
+
+    /// Whether the input method is kept enabled for the focused window, so
+    /// the text it commits is delivered as [`input_method::Event::Commit`]
+    /// events even when no widget requested it (e.g. for an overlay that
+    /// searches whatever is typed into it).
+    ///
+    /// Only the committed text is delivered without a request; the preedit
+    /// is left to the input method, since no widget would draw it.
+    ///
+    /// By default, the input method is only enabled while a widget requests
+    /// it.
+    ///
+    /// [`input_method::Event::Commit`]: crate::core::input_method::Event::Commit
+    pub capture_input_method: bool,
+
+    /// The application identifier of the surfaces, the Wayland equivalent of
+    /// the X11 `WM_CLASS`.
+    ///
+    /// Compositors and tools use it to match rules (e.g. window placement in
+    /// tiling compositors) and desktop entries, so it should match the name
+    /// of the `.desktop` file of the application. It is used as the
+    /// namespace of layer surfaces that do not specify one, since it cannot
+    /// be changed once the surface has been committed.
+    ///
+    /// If unset, the `application_id` of the platform specific window
+    /// settings is used for the main window instead.
+    pub application_id: Option<String>,
+
+    /// Whether the clipboard keeps its contents in memory instead of using
+    /// the selections of the compositor.
+    ///
+    /// This makes copy and paste deterministic in integration tests, where
+    /// no real clipboard is available.
+    #[cfg(feature = "memory-clipboard")]
+    pub memory_clipboard: bool,
+
+    /// The callback invoked with the state transitions of the surfaces, like
+    /// configures, focus and scale changes and presentation results.
+    ///
+    /// Trace events are not even built when this is unset, which is the
+    /// default.
+    pub trace: Option<Trace>,
+}
+
+impl Settings {
+    /// Invokes the [`Trace`] callback, if any, with the produced event.
+    pub(crate) fn trace(&self, event: impl FnOnce() -> trace::Event) {
+        if let Some(trace) = &self.trace {
+            trace.call(&event());
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Embed separately composited content within a surface.
+//!
+//! A sub-surface is a `wl_surface` that is positioned relative to a parent
+//! surface and composited by the compositor on top of it, which allows content
+//! rendered by another pipeline (e.g. a hardware video decoder) to be shown
+//! inside a window without going through the renderer of iced.
+use std::{
+    ffi::c_void,
+    ptr::NonNull,
+    sync::atomic::{self, AtomicU64},
+};
+
+use sctk::reexports::client::{
+    protocol::{wl_display::WlDisplay, wl_subsurface::WlSubsurface, wl_surface::WlSurface},
+    Proxy,
+};
+
+use crate::{
+    action,
+    core::{window, Point},
+    runtime::{
+        window::raw_window_handle::{
+            DisplayHandle, HandleError, HasDisplayHandle, HasWindowHandle, RawDisplayHandle,
+            RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle, WindowHandle,
+        },
+        Task,
+    },
+};
+
+/// The identifier of a [`Subsurface`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Id(u64);
+
+static COUNT: AtomicU64 = AtomicU64::new(0);
+
+impl Id {
+    fn unique() -> Self {
+        Id(COUNT.fetch_add(1, atomic::Ordering::Relaxed))
+    }
+}
+
+/// A sub-surface embedded within a window.
+///
+/// Its raw window handle can be given to an external renderer, which is then
+/// responsible for attaching buffers to it and committing them.
+#[derive(Debug, Clone)]
+pub struct Subsurface {
+    id: Id,
+    display: WlDisplay,
+    surface: WlSurface,
+}
+
+impl Subsurface {
+    /// Returns the [`Id`] of the [`Subsurface`].
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Returns the `wl_surface` of the [`Subsurface`].
+    pub fn wl_surface(&self) -> &WlSurface {
+        &self.surface
+    }
+}
+
+impl HasDisplayHandle for Subsurface {
+    fn display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
+        let display = self.display.id().as_ptr() as *mut c_void;
+
+        let c_ptr = NonNull::new(display).ok_or(HandleError::Unavailable)?;
+        let handle = WaylandDisplayHandle::new(c_ptr);
+        let raw_handle = RawDisplayHandle::Wayland(handle);
+        #[allow(unsafe_code)]
+        Ok(unsafe { DisplayHandle::borrow_raw(raw_handle) })
+    }
+}
+
+impl HasWindowHandle for Subsurface {
+    fn window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
+        let surface = self.surface.id().as_ptr() as *mut c_void;
+        let c_ptr = NonNull::new(surface).ok_or(HandleError::Unavailable)?;
+        let handle = WaylandWindowHandle::new(c_ptr);
+        let raw_handle = RawWindowHandle::Wayland(handle);
+        #[allow(unsafe_code)]
+        Ok(unsafe { WindowHandle::borrow_raw(raw_handle) })
+    }
+}
+
+/// An error that occurred while creating a [`Subsurface`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum CreateError {
+    /// The parent window does not exist.
+    #[error("the parent window was not found")]
+    WindowNotFound,
+
+    /// The compositor does not support sub-surfaces (`wl_subcompositor`).
+    #[error("the compositor does not support sub-surfaces")]
+    Unsupported,
+}
+
+/// Creates a [`Subsurface`] within the window with the given [`window::Id`],
+/// with its top-left corner at the given position in logical coordinates.
+///
+/// The position is relative to the parent, so the sub-surface moves along
+/// with it. The sub-surface is synchronized: a buffer committed to it is only
+/// shown once the parent is redrawn, so both are updated in the same frame
+/// when the window is resized.
+pub fn create(parent: window::Id, position: Point<i32>) -> Task<Result<Subsurface, CreateError>> {
+    action::oneshot(move |channel| action::Action::CreateSubsurface(parent, position, channel))
+}
+
+/// Moves the [`Subsurface`] with the given [`Id`] within its parent window.
+///
+/// The new position is applied on the next frame of the parent, so it can be
+/// changed together with the layout of the window.
+pub fn set_position<T>(id: Id, position: Point<i32>) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetSubsurfacePosition(id, position))
+}
+
+/// Destroys the [`Subsurface`] with the given [`Id`].
+///
+/// Sub-surfaces are also destroyed along with their parent window.
+pub fn destroy<T>(id: Id) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::DestroySubsurface(id))
+}
+
+/// The protocol objects of a [`Subsurface`], owned by its parent window.
+#[derive(Debug)]
+pub(crate) struct Embedded {
+    subsurface: WlSubsurface,
+    surface: WlSurface,
+}
+
+impl Embedded {
+    /// Embeds the `wl_surface` of a newly created sub-surface at the given
+    /// position, returning the [`Subsurface`] handed to the program.
+    pub fn new(
+        display: WlDisplay,
+        subsurface: WlSubsurface,
+        surface: WlSurface,
+        position: Point<i32>,
+    ) -> (Self, Subsurface) {
+        subsurface.set_position(position.x, position.y);
+        subsurface.set_sync();
+
+        let handle = Subsurface {
+            id: Id::unique(),
+            display,
+            surface: surface.clone(),
+        };
+
+        (
+            Self {
+                subsurface,
+                surface,
+            },
+            handle,
+        )
+    }
+
+    /// Moves the sub-surface, effective on the next commit of the parent.
+    pub fn set_position(&self, position: Point<i32>) {
+        self.subsurface.set_position(position.x, position.y);
+    }
+
+    /// Destroys the role object first and then the `wl_surface`.
+    pub fn destroy(self) {
+        self.subsurface.destroy();
+        self.surface.destroy();
+    }
+}
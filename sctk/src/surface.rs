@@ -0,0 +1,241 @@
+//! Control the surfaces of your application.
+use std::time::Duration;
+
+use crate::{
+    action,
+    core::{window::Id, Padding},
+    runtime::Task,
+};
+
+/// Blurs whatever is behind the surface with the given [`Id`], or stops
+/// blurring it.
+///
+/// The whole surface is blurred, so the blurred area follows its size. This
+/// only makes a difference where the surface is translucent, like the
+/// background of a bar with an alpha below `1.0`. It requires the `blur`
+/// feature and is a no-op if the compositor does not support
+/// `org_kde_kwin_blur_manager`, as is the case outside of KDE Plasma.
+#[cfg(feature = "blur")]
+pub fn set_blur<T>(id: Id, blur: bool) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetBlur(id, blur))
+}
+
+/// Overrides the theme of the surface with the given [`Id`], ignoring the
+/// theme of the program until [`unset_theme`] is called.
+///
+/// The `Theme` must be the theme type of the running program; otherwise the
+/// override is ignored.
+pub fn set_theme<Theme, T>(id: Id, theme: Theme) -> Task<T>
+where
+    Theme: Send + 'static,
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetTheme(id, Some(Box::new(theme))))
+}
+
+/// Clears the theme override of the surface with the given [`Id`], so it
+/// follows the theme of the program again.
+pub fn unset_theme<T>(id: Id) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetTheme(id, None))
+}
+
+/// Overrides the scale factor of the surface with the given [`Id`], ignoring
+/// the scale factor of the program until [`unset_scale_factor`] is called.
+///
+/// The scale factor is applied on top of the scale of the output, so a
+/// factor of `1.25` enlarges the user interface regardless of the DPI, which
+/// is useful for an accessibility toggle.
+pub fn set_scale_factor<T>(id: Id, scale_factor: f64) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetScaleFactor(id, Some(scale_factor)))
+}
+
+/// Clears the scale factor override of the surface with the given [`Id`], so
+/// it follows the scale factor of the program again.
+pub fn unset_scale_factor<T>(id: Id) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetScaleFactor(id, None))
+}
+
+/// Sets the opacity of the surface with the given [`Id`], where `0.0` is fully
+/// transparent and `1.0` is fully opaque.
+///
+/// The opacity is applied by the compositor, so fading a surface does not
+/// require rendering its user interface again. If the compositor does not
+/// support `wp_alpha_modifier_v1`, the opacity is applied to the background
+/// color of the surface instead, which is redrawn: the background fades, but
+/// the widgets drawn on top of it stay opaque.
+pub fn set_alpha<T>(id: Id, alpha: f32) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetAlpha(id, alpha))
+}
+
+/// How the frames of a surface are presented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentationHint {
+    /// Frames are synchronized with the vertical blank of the output, which
+    /// avoids tearing.
+    #[default]
+    Vsync,
+
+    /// Frames are presented as soon as possible, which may tear but
+    /// minimizes latency (e.g. for games).
+    Async,
+}
+
+/// Restricts the input region of the surface with the given [`Id`] to its
+/// area within the given margin, in logical pixels, or lets the whole surface
+/// receive input again.
+///
+/// The pointer and touch events over the margin go to whatever is behind
+/// the surface, which is what a surface drawing its own shadow needs. The
+/// region follows the size of the surface.
+pub fn set_input_margin<T>(id: Id, margin: Option<Padding>) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetInputMargin(id, margin))
+}
+
+/// Sets the [`PresentationHint`] of the surface with the given [`Id`].
+///
+/// The compositor is free to ignore the hint, and this is a no-op if it does
+/// not support `wp_tearing_control_v1`. Surfaces are presented with
+/// [`PresentationHint::Vsync`] by default.
+pub fn set_presentation_hint<T>(id: Id, hint: PresentationHint) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetPresentationHint(id, hint))
+}
+
+/// Confines the pointers that are over the surface with the given [`Id`] to
+/// its bounds, or releases them.
+///
+/// This keeps the cursor from leaving the surface during an interaction,
+/// like dragging a slider. A pointer is released once its buttons are
+/// released or it leaves the surface, and the compositor releases the
+/// pointers on its own when the surface loses focus.
+/// This is a no-op if the compositor does not support
+/// `zwp_pointer_constraints_v1`.
+pub fn confine_pointer<T>(id: Id, confine: bool) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::ConfinePointer(id, confine))
+}
+
+/// Waits until the next frame of the surface with the given [`Id`] has been
+/// presented, requesting it if the surface is idle.
+///
+/// This is a plain "a frame went out" signal for sequencing work after
+/// presentation, like capturing the surface or handing it over to an
+/// external renderer. It does not tell when the compositor shows the frame
+/// on screen. Frames that fail to be presented are not counted, and nothing
+/// is produced if the surface is closed first.
+pub fn presented(id: Id) -> Task<()> {
+    action::oneshot(move |channel| action::Action::WaitPresent(id, channel))
+}
+
+/// Commits the pending state of the surface with the given [`Id`] right
+/// away.
+///
+/// This is a low-level escape hatch for interoperating with content that the
+/// shell does not manage, like the buffers an external renderer attaches to
+/// a synchronized [`Subsurface`], which only show up once their parent is
+/// committed. Normal applications never need it: the shell commits along
+/// with every frame and state change. A commit applies all the pending state
+/// of the surface, so one made while another client of the surface is
+/// halfway through updating it can apply a partial state, or cause a
+/// protocol error that terminates the connection.
+///
+/// [`Subsurface`]: crate::subsurface::Subsurface
+pub fn commit<T>(id: Id) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::Commit(id))
+}
+
+/// Listens to the visibility of the surface with the given [`Id`], producing
+/// `false` when it becomes occluded and `true` when it is visible again.
+///
+/// Compositors stop sending frames to surfaces that are not shown (e.g. on
+/// another workspace), so a surface is considered occluded when a requested
+/// frame does not arrive within a second. An application can pause its
+/// animations in the meantime to save power. Surfaces that do not request
+/// frames are never reported as occluded.
+pub fn visibility(id: Id) -> Task<bool> {
+    action::stream(move |channel| action::Action::ListenVisibility(id, channel))
+}
+
+/// Fetches whether the compositor is throttling the frames of the surface
+/// with the given [`Id`], which is the case when its last requested frame
+/// has not arrived within a second.
+///
+/// This is the same test as [`visibility`], taken at a single moment, so an
+/// animation driven by a timer instead of by the frames of the surface can
+/// check it before ticking. Nothing is produced if the surface is closed.
+pub fn is_throttled(id: Id) -> Task<bool> {
+    action::oneshot(move |channel| action::Action::GetThrottled(id, channel))
+}
+
+/// Hides the cursor after it has not moved over the surface with the given
+/// [`Id`] for the given timeout, showing it again as soon as it moves.
+///
+/// This is useful for media players and presentations. Passing `None`
+/// disables auto-hiding, which is the default.
+pub fn set_cursor_auto_hide<T>(id: Id, timeout: Option<Duration>) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetCursorAutoHide(id, timeout))
+}
+
+/// Suspends the redraws of the surface with the given [`Id`] until
+/// [`resume_redraws`] is called, so a batch of updates (e.g. while loading a
+/// large image) produces a single frame instead of intermediate states.
+///
+/// Redraws resume on their own once the given timeout elapses, so a surface
+/// can never stay frozen.
+pub fn suspend_redraws<T>(id: Id, timeout: Duration) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SuspendRedraws(id, timeout))
+}
+
+/// Limits the surface with the given [`Id`] to redraw at most once every
+/// given interval, regardless of how often a redraw is requested.
+///
+/// This saves power for surfaces that do not need to be smooth, like an
+/// idle clock that only needs to tick once per second, while other surfaces
+/// keep redrawing freely. Passing `None` removes the limit, which is the
+/// default.
+pub fn set_min_redraw_interval<T>(id: Id, interval: Option<Duration>) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::SetMinRedrawInterval(id, interval))
+}
+
+/// Resumes the redraws of the surface with the given [`Id`], drawing it once
+/// if it needed to be redrawn in the meantime.
+pub fn resume_redraws<T>(id: Id) -> Task<T>
+where
+    T: Send + 'static,
+{
+    action::effect(action::Action::ResumeRedraws(id))
+}
@@ -0,0 +1,326 @@
+//! Connect input methods through `zwp_text_input_v3`.
+use sctk::reexports::{
+    client::{protocol::wl_surface::WlSurface, Connection, Dispatch, QueueHandle},
+    protocols::wp::text_input::zv3::client::zwp_text_input_v3::{
+        self, ChangeCause, ZwpTextInputV3,
+    },
+};
+
+use crate::{
+    conversion,
+    core::{self, input_method, Rectangle},
+    input_method::SurroundingText,
+    program::Program,
+    State,
+};
+
+/// The request of a window for an input method, in surface-local
+/// coordinates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    /// The rectangle of the text caret, which the input method places its
+    /// candidate window next to.
+    pub cursor: Rectangle<i32>,
+    pub purpose: input_method::Purpose,
+    /// The text around the caret, if the application provided it.
+    pub surrounding_text: Option<SurroundingText>,
+}
+
+/// The text input of a seat.
+#[derive(Debug)]
+pub struct TextInput {
+    raw: ZwpTextInputV3,
+    focus: Option<WlSurface>,
+    enabled: Option<Request>,
+    is_enabled: bool,
+    captures: bool,
+    composition: Composition,
+}
+
+impl TextInput {
+    /// Creates the [`TextInput`] of a seat, which is kept enabled for the
+    /// focused window without a [`Request`] if it `captures` the input
+    /// method.
+    pub fn new(raw: ZwpTextInputV3, captures: bool) -> Self {
+        Self {
+            raw,
+            focus: None,
+            enabled: None,
+            is_enabled: false,
+            captures,
+            composition: Composition::default(),
+        }
+    }
+
+    pub fn focus(&self) -> Option<&WlSurface> {
+        self.focus.as_ref()
+    }
+
+    /// Synchronizes the state of the input method with the [`Request`] of the
+    /// focused window, returning the event that the window should receive,
+    /// if any.
+    pub fn update(&mut self, request: Option<Request>) -> Option<input_method::Event> {
+        let enable = request.is_some() || self.captures;
+
+        if self.focus.is_none() || (self.enabled == request && self.is_enabled == enable) {
+            return None;
+        }
+
+        let event = match (&self.enabled, &request) {
+            (None, Some(_)) => Some(input_method::Event::Opened),
+            (Some(_), None) => {
+                self.composition.is_preediting = false;
+                Some(input_method::Event::Closed)
+            }
+            _ => None,
+        };
+
+        match (self.is_enabled, enable) {
+            (false, true) => self.raw.enable(),
+            (true, false) => self.raw.disable(),
+            _ => {}
+        }
+
+        if let Some(request) = &request {
+            let (hint, purpose) = conversion::input_method::content_type(request.purpose);
+            self.raw.set_content_type(hint, purpose);
+
+            let cursor = request.cursor;
+            self.raw
+                .set_cursor_rectangle(cursor.x, cursor.y, cursor.width, cursor.height);
+
+            if let Some(surrounding_text) = &request.surrounding_text {
+                let (text, cursor, anchor) = surrounding_text.truncated();
+                self.raw
+                    .set_surrounding_text(text.to_owned(), cursor, anchor);
+
+                // The text changed because of the last commit of the input
+                // method, unless the application edited it since.
+                self.raw
+                    .set_text_change_cause(if self.composition.has_committed {
+                        ChangeCause::InputMethod
+                    } else {
+                        ChangeCause::Other
+                    });
+                self.composition.has_committed = false;
+            }
+        } else if enable {
+            let (hint, purpose) =
+                conversion::input_method::content_type(input_method::Purpose::Normal);
+            self.raw.set_content_type(hint, purpose);
+        }
+
+        self.raw.commit();
+        self.composition.commits = self.composition.commits.wrapping_add(1);
+        self.enabled = request;
+        self.is_enabled = enable;
+
+        event
+    }
+
+    pub fn destroy(self) {
+        self.raw.destroy();
+    }
+}
+
+/// The composition of an input method, whose changes are batched until the
+/// compositor applies them atomically with `done`.
+#[derive(Debug, Default)]
+struct Composition {
+    /// The number of `commit` requests made, which `done` echoes once the
+    /// compositor has processed them all.
+    commits: u32,
+    pending_preedit: Option<(String, i32, i32)>,
+    pending_commit: Option<String>,
+    pending_deletion: Option<(u32, u32)>,
+    is_preediting: bool,
+    has_committed: bool,
+}
+
+impl Composition {
+    /// Applies the batched changes, in the order required by the protocol,
+    /// returning the events that the focused window should receive.
+    ///
+    /// The preedit is only reported if a widget `shows_preedit`.
+    fn done(&mut self, serial: u32, shows_preedit: bool) -> Vec<input_method::Event> {
+        let preedit = self.pending_preedit.take();
+        let commit = self.pending_commit.take();
+        let deletion = self.pending_deletion.take();
+
+        let mut events = Vec::new();
+
+        // A batch sent before the compositor saw the latest state is still
+        // applied, but does not change the state of the text input.
+        if serial == self.commits {
+            self.has_committed |= commit.is_some();
+        }
+
+        // 1. The current preedit is removed, or replaced by the new one if
+        //    nothing is committed.
+        if shows_preedit && self.is_preediting && (commit.is_some() || deletion.is_some()) {
+            self.is_preediting = false;
+            events.push(input_method::Event::Preedit(String::new(), None));
+        }
+
+        // 2. The text around the cursor is deleted. Widgets cannot be told to
+        //    delete text, so input methods that reconvert the surrounding
+        //    text may duplicate it.
+        if let Some((before, after)) = deletion {
+            log::debug!(
+                "The input method deleted {before} bytes before and {after} bytes after the \
+                cursor, which widgets do not support."
+            );
+        }
+
+        // 3. The committed text is inserted.
+        if let Some(commit) = commit {
+            events.push(input_method::Event::Commit(commit));
+        }
+
+        // 4. The new preedit is inserted. An empty one clears the one that
+        //    is being composed.
+        if shows_preedit && (preedit.is_some() || self.is_preediting) {
+            let (content, selection) = match preedit {
+                Some((content, begin, end)) => {
+                    let selection = (begin >= 0 && end >= 0).then(|| begin as usize..end as usize);
+
+                    (content, selection)
+                }
+                None => (String::new(), None),
+            };
+
+            self.is_preediting = !content.is_empty();
+            events.push(input_method::Event::Preedit(content, selection));
+        }
+
+        events
+    }
+}
+
+impl<P: Program + 'static> Dispatch<ZwpTextInputV3, ()> for State<P> {
+    fn event(
+        state: &mut Self,
+        proxy: &ZwpTextInputV3,
+        event: zwp_text_input_v3::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(text_input) = state
+            .text_inputs
+            .values_mut()
+            .find(|text_input| text_input.raw == *proxy)
+        else {
+            return;
+        };
+
+        match event {
+            zwp_text_input_v3::Event::Enter { surface } => {
+                // The state of the input method is reset on every enter.
+                text_input.focus = Some(surface);
+                text_input.enabled = None;
+                text_input.is_enabled = false;
+                text_input.composition.has_committed = false;
+                state.sync_text_inputs();
+            }
+            zwp_text_input_v3::Event::Leave { surface } => {
+                let was_enabled = text_input.enabled.take().is_some();
+                text_input.focus = None;
+                text_input.is_enabled = false;
+                text_input.composition.is_preediting = false;
+
+                if was_enabled && let Some((id, _)) = state.window_manager.get_mut_alias(&surface) {
+                    state
+                        .events
+                        .push((id, core::Event::InputMethod(input_method::Event::Closed)));
+                }
+            }
+            zwp_text_input_v3::Event::PreeditString {
+                text,
+                cursor_begin,
+                cursor_end,
+            } => {
+                text_input.composition.pending_preedit =
+                    text.map(|text| (text, cursor_begin, cursor_end));
+            }
+            zwp_text_input_v3::Event::CommitString { text } => {
+                text_input.composition.pending_commit = text;
+            }
+            zwp_text_input_v3::Event::DeleteSurroundingText {
+                before_length,
+                after_length,
+            } => {
+                text_input.composition.pending_deletion = Some((before_length, after_length));
+            }
+            zwp_text_input_v3::Event::Done { serial } => {
+                let shows_preedit = text_input.enabled.is_some();
+                let events = text_input.composition.done(serial, shows_preedit);
+
+                if let Some((id, _)) = text_input
+                    .focus
+                    .as_ref()
+                    .and_then(|surface| state.window_manager.get_mut_alias(surface))
+                {
+                    state.events.extend(
+                        events
+                            .into_iter()
+                            .map(|event| (id, core::Event::InputMethod(event))),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_are_applied_in_order_on_done() {
+        let mut composition = Composition {
+            commits: 2,
+            ..Composition::default()
+        };
+
+        composition.pending_preedit = Some((String::from("かん"), 6, 6));
+        assert_eq!(
+            composition.done(2, true),
+            [input_method::Event::Preedit(
+                String::from("かん"),
+                Some(6..6)
+            )]
+        );
+
+        // Nothing is applied until `done`, and the preedit that is being
+        // composed is removed before the commit is inserted.
+        composition.pending_preedit = Some((String::from("じ"), 3, 3));
+        composition.pending_commit = Some(String::from("漢"));
+        assert!(composition.is_preediting);
+
+        assert_eq!(
+            composition.done(2, true),
+            [
+                input_method::Event::Preedit(String::new(), None),
+                input_method::Event::Commit(String::from("漢")),
+                input_method::Event::Preedit(String::from("じ"), Some(3..3)),
+            ]
+        );
+        assert!(composition.has_committed);
+
+        // A stale batch is still applied, without changing the state.
+        composition.has_committed = false;
+        composition.pending_commit = Some(String::from("字"));
+
+        assert_eq!(
+            composition.done(1, true),
+            [
+                input_method::Event::Preedit(String::new(), None),
+                input_method::Event::Commit(String::from("字")),
+            ]
+        );
+        assert!(!composition.has_committed);
+        assert!(!composition.is_preediting);
+    }
+}
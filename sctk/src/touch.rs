@@ -0,0 +1,251 @@
+//! Recognize touch gestures for widgets that only understand the mouse.
+//!
+//! Taps and drags are already handled by the widgets from the finger events
+//! themselves. A long press, however, has no equivalent: once recognized, the
+//! finger is reported as lost and continues as a mouse double click that is
+//! held down, so text widgets select the word under the finger and extend the
+//! selection as it is dragged.
+use std::time::Duration;
+
+use crate::core::{mouse, time::Instant, touch, window, Event, Point};
+
+/// The thresholds of the touch gestures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// How long a finger must be held in place to be recognized as a long
+    /// press.
+    pub long_press: Duration,
+
+    /// How far a finger may move, in logical pixels, before it is considered
+    /// a drag instead of a long press.
+    pub slop: f32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            long_press: Duration::from_millis(500),
+            slop: 8.0,
+        }
+    }
+}
+
+/// Recognizes the long press of the first finger that touches a window.
+///
+/// Positions are in the coordinates of the user interface, like the mouse
+/// events the gestures are turned into.
+#[derive(Debug)]
+pub(crate) struct Recognizer {
+    thresholds: Thresholds,
+    primary: Option<Primary>,
+}
+
+#[derive(Debug)]
+struct Primary {
+    touch_id: i32,
+    window: window::Id,
+    start: Point,
+    position: Point,
+    pressed_at: Instant,
+    is_selecting: bool,
+}
+
+impl Recognizer {
+    pub fn new(thresholds: Thresholds) -> Self {
+        Self {
+            thresholds,
+            primary: None,
+        }
+    }
+
+    /// Tracks a finger touching a window, if no other finger is down.
+    pub fn press(&mut self, window: window::Id, touch_id: i32, position: Point, now: Instant) {
+        if self.primary.is_none() {
+            self.primary = Some(Primary {
+                touch_id,
+                window,
+                start: position,
+                position,
+                pressed_at: now,
+                is_selecting: false,
+            });
+        }
+    }
+
+    /// Moves a finger, returning whether its event was turned into mouse
+    /// events and must not be reported.
+    pub fn motion(
+        &mut self,
+        touch_id: i32,
+        position: Point,
+        events: &mut Vec<(window::Id, Event)>,
+    ) -> bool {
+        let Some(primary) = self.primary.as_mut().filter(|p| p.touch_id == touch_id) else {
+            return false;
+        };
+
+        primary.position = position;
+
+        if primary.is_selecting {
+            events.push((
+                primary.window,
+                Event::Mouse(mouse::Event::CursorMoved { position }),
+            ));
+
+            return true;
+        }
+
+        // The finger is dragging instead (e.g. to scroll).
+        if primary.start.distance(position) > self.thresholds.slop {
+            self.primary = None;
+        }
+
+        false
+    }
+
+    /// Lifts a finger, returning whether its event was turned into mouse
+    /// events and must not be reported.
+    pub fn lift(&mut self, touch_id: i32, events: &mut Vec<(window::Id, Event)>) -> bool {
+        if self
+            .primary
+            .as_ref()
+            .is_none_or(|primary| primary.touch_id != touch_id)
+        {
+            return false;
+        }
+
+        let Some(primary) = self.primary.take() else {
+            return false;
+        };
+
+        if primary.is_selecting {
+            events.push((
+                primary.window,
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)),
+            ));
+        }
+
+        primary.is_selecting
+    }
+
+    /// Forgets every finger, like when the compositor cancels the touch
+    /// sequence.
+    pub fn cancel(&mut self, events: &mut Vec<(window::Id, Event)>) {
+        if let Some(primary) = self.primary.take()
+            && primary.is_selecting
+        {
+            events.push((
+                primary.window,
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)),
+            ));
+        }
+    }
+
+    /// Returns the instant at which the finger that is down becomes a long
+    /// press, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.primary
+            .as_ref()
+            .filter(|primary| !primary.is_selecting)
+            .map(|primary| primary.pressed_at + self.thresholds.long_press)
+    }
+
+    /// Recognizes the long press of the finger that is still held in place.
+    pub fn expire(&mut self, now: Instant, events: &mut Vec<(window::Id, Event)>) {
+        let Some(deadline) = self.deadline() else {
+            return;
+        };
+
+        let Some(primary) = self.primary.as_mut().filter(|_| deadline <= now) else {
+            return;
+        };
+
+        primary.is_selecting = true;
+
+        let window = primary.window;
+        let position = primary.position;
+
+        events.extend(
+            [
+                Event::Touch(touch::Event::FingerLost {
+                    id: touch::Finger(primary.touch_id as u64),
+                    position,
+                }),
+                Event::Mouse(mouse::Event::CursorMoved { position }),
+                Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)),
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)),
+                Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)),
+            ]
+            .into_iter()
+            .map(|event| (window, event)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recognizer() -> (Recognizer, window::Id, Instant) {
+        let mut recognizer = Recognizer::new(Thresholds::default());
+        let window = window::Id::unique();
+        let now = Instant::now();
+
+        recognizer.press(window, 0, Point::new(10.0, 10.0), now);
+
+        (recognizer, window, now)
+    }
+
+    #[test]
+    fn held_finger_selects_and_extends() {
+        let (mut recognizer, window, now) = recognizer();
+        let mut events = Vec::new();
+
+        recognizer.expire(now + Duration::from_millis(100), &mut events);
+        assert!(events.is_empty());
+
+        recognizer.expire(now + Duration::from_secs(1), &mut events);
+        assert_eq!(events.len(), 5);
+        assert_eq!(recognizer.deadline(), None);
+
+        events.clear();
+        let position = Point::new(50.0, 10.0);
+        assert!(recognizer.motion(0, position, &mut events));
+        assert_eq!(
+            events,
+            [(window, Event::Mouse(mouse::Event::CursorMoved { position }))]
+        );
+
+        events.clear();
+        assert!(recognizer.lift(0, &mut events));
+        assert_eq!(
+            events,
+            [(
+                window,
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+            )]
+        );
+    }
+
+    #[test]
+    fn dragged_finger_is_not_a_long_press() {
+        let (mut recognizer, _, now) = recognizer();
+        let mut events = Vec::new();
+
+        assert!(!recognizer.motion(0, Point::new(40.0, 10.0), &mut events));
+        recognizer.expire(now + Duration::from_secs(1), &mut events);
+
+        assert!(events.is_empty());
+        assert!(!recognizer.lift(0, &mut events));
+    }
+
+    #[test]
+    fn short_tap_is_left_to_the_widgets() {
+        let (mut recognizer, _, _) = recognizer();
+        let mut events = Vec::new();
+
+        assert!(!recognizer.lift(0, &mut events));
+        assert!(events.is_empty());
+        assert_eq!(recognizer.deadline(), None);
+    }
+}
@@ -0,0 +1,72 @@
+//! Trace the state transitions of the surfaces of the shell.
+//!
+//! This is meant to diagnose issues with the compositor (e.g. a surface that
+//! never appears) without enabling `WAYLAND_DEBUG` for the whole process.
+use std::{fmt, sync::Arc};
+
+use crate::{
+    core::{window, Size},
+    graphics::compositor::SurfaceError,
+};
+
+/// A state transition of a surface.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// The compositor configured a surface.
+    Configured {
+        /// The [`window::Id`] of the surface.
+        id: window::Id,
+        /// The size of the surface, in logical pixels.
+        size: Size<u32>,
+        /// Whether this is the initial configure, which maps the surface.
+        initial: bool,
+    },
+
+    /// A surface gained the focus of a keyboard.
+    Focused(window::Id),
+
+    /// A surface lost the focus of every keyboard.
+    Unfocused(window::Id),
+
+    /// The buffer scale of a surface changed.
+    ScaleChanged {
+        /// The [`window::Id`] of the surface.
+        id: window::Id,
+        /// The new buffer scale.
+        scale: u32,
+    },
+
+    /// The graphics compositor was recreated, along with the surfaces and
+    /// renderers of every window, after a surface or its device was lost
+    /// (e.g. when the graphics driver was reset or on resume).
+    CompositorRecreated,
+
+    /// A frame of a surface was presented.
+    Presented {
+        /// The [`window::Id`] of the surface.
+        id: window::Id,
+        /// The error that occurred while presenting the frame, if any.
+        error: Option<SurfaceError>,
+    },
+}
+
+/// A callback invoked with every trace [`Event`] of the shell.
+#[derive(Clone)]
+pub struct Trace(Arc<dyn Fn(&Event) + Send + Sync>);
+
+impl Trace {
+    /// Creates a [`Trace`] from the given callback.
+    pub fn new(f: impl Fn(&Event) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn call(&self, event: &Event) {
+        (self.0)(event);
+    }
+}
+
+impl fmt::Debug for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Trace").finish_non_exhaustive()
+    }
+}
@@ -1,8 +1,12 @@
+mod aliases;
 mod state;
 
-use std::{collections::BTreeMap, ffi::c_void, ptr::NonNull, rc::Rc};
+use std::{collections::BTreeMap, ffi::c_void, mem, ptr::NonNull, rc::Rc, time::Duration};
 
-use iced_debug::core::{alignment, renderer, text, Color, Padding, Rectangle, Text, Vector};
+use aliases::Aliases;
+use iced_debug::core::{
+    alignment, renderer, text, Color, Padding, Pixels, Rectangle, Text, Vector,
+};
 use iced_program::{
     graphics::compositor,
     runtime::window::raw_window_handle::{
@@ -12,20 +16,47 @@ use iced_program::{
 };
 use rustc_hash::FxHashMap;
 use sctk::{
-    reexports::client::{
-        protocol::{wl_display::WlDisplay, wl_pointer::WlPointer, wl_surface::WlSurface},
-        Connection, Proxy, QueueHandle,
+    compositor::{CompositorState, Region},
+    reexports::{
+        client::{
+            protocol::{
+                wl_display::WlDisplay, wl_output::WlOutput, wl_pointer::WlPointer,
+                wl_surface::WlSurface,
+            },
+            Connection, Proxy, QueueHandle,
+        },
+        protocols::wp::{
+            alpha_modifier::v1::client::{
+                wp_alpha_modifier_surface_v1::WpAlphaModifierSurfaceV1,
+                wp_alpha_modifier_v1::WpAlphaModifierV1,
+            },
+            pointer_constraints::zv1::client::zwp_confined_pointer_v1::ZwpConfinedPointerV1,
+            tearing_control::v1::client::{
+                wp_tearing_control_manager_v1::WpTearingControlManagerV1,
+                wp_tearing_control_v1::{self, WpTearingControlV1},
+            },
+        },
     },
     seat::pointer::ThemedPointer,
     shell::{wlr_layer::LayerSurface, WaylandSurface},
 };
 use state::State;
 
+#[cfg(feature = "dmabuf-feedback")]
+use sctk::reexports::protocols::wp::linux_dmabuf::zv1::client::zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1;
+#[cfg(feature = "blur")]
+use wayland_protocols_plasma::blur::client::{
+    org_kde_kwin_blur::OrgKdeKwinBlur, org_kde_kwin_blur_manager::OrgKdeKwinBlurManager,
+};
+
 pub use crate::core::window::{Id, RedrawRequest};
 use crate::{
-    core::{input_method, mouse, theme, time::Instant, InputMethod, Point, Size},
+    conversion,
+    core::{input_method, layer_shell, mouse, theme, time::Instant, InputMethod, Point, Size},
+    futures::futures::channel::{mpsc, oneshot},
     graphics::Compositor,
     program::{self, Program},
+    subsurface, text_input,
 };
 
 pub struct WindowManager<P>
@@ -33,7 +64,7 @@ where
     P: Program + 'static,
     P::Theme: theme::Base,
 {
-    aliases: FxHashMap<WlSurface, Id>,
+    aliases: Aliases<WlSurface>,
     entries: BTreeMap<Id, Window<P>>,
 }
 
@@ -44,7 +75,7 @@ where
 {
     pub fn new() -> Self {
         Self {
-            aliases: FxHashMap::default(),
+            aliases: Aliases::new(),
             entries: BTreeMap::new(),
         }
     }
@@ -55,18 +86,25 @@ where
         conn: Connection,
         qh: QueueHandle<crate::State<P>>,
         window: RawWindow,
+        layer_settings: Option<layer_shell::Settings>,
         surface_size: Size<u32>,
+        settings: &crate::Settings,
         program: &program::Instance<P>,
         compositor: &mut <P::Renderer as compositor::Default>::Compositor,
     ) -> &mut Window<P> {
-        let state = State::new(program, id, surface_size);
+        let state = State::new(program, id, surface_size, settings.snap_to_pixels);
         let viewport_version = state.viewport_version();
 
         let surface =
             compositor.create_surface(window.clone(), surface_size.width, surface_size.height);
         let renderer = compositor.create_renderer();
 
-        let _ = self.aliases.insert(window.surface().clone(), id);
+        debug_assert!(
+            !self.entries.contains_key(&id),
+            "window {id:?} was inserted twice"
+        );
+
+        self.aliases.insert(window.surface().clone(), id);
 
         let _ = self.entries.insert(
             id,
@@ -74,6 +112,7 @@ where
                 qh,
                 conn,
                 raw: window,
+                layer_settings,
                 state,
                 viewport_version,
                 surface,
@@ -81,8 +120,37 @@ where
                 mouse_interaction: mouse::Interaction::None,
                 redraw_at: RedrawRequest::Wait,
                 pointers: FxHashMap::default(),
+                held_buttons: Vec::new(),
                 preedit: None,
                 ime_state: None,
+                surrounding_text: None,
+                alpha_modifier: None,
+                tearing_control: None,
+                confined_pointers: Vec::new(),
+                raised_interactivity: false,
+                paste: None,
+                cursor_auto_hide: None,
+                cursor_hide_at: None,
+                cursor_hidden: false,
+                redraws_suspended_until: None,
+                redraw_deferred: false,
+                subsurfaces: FxHashMap::default(),
+                outputs: Vec::new(),
+                output_requests: Vec::new(),
+                present_requests: Vec::new(),
+                min_redraw_interval: None,
+                last_redraw_at: None,
+                auto_exclusive_zone: false,
+                input_margin: None,
+                shrink_listeners: Vec::new(),
+                frame_requested_at: None,
+                first_frame_at: Some(Instant::now() + FIRST_FRAME_TIMEOUT),
+                is_occluded: false,
+                visibility_listeners: Vec::new(),
+                #[cfg(feature = "dmabuf-feedback")]
+                dmabuf_feedback: None,
+                #[cfg(feature = "blur")]
+                blur: None,
             },
         );
 
@@ -93,18 +161,33 @@ where
         self.entries.is_empty()
     }
 
+    /// Returns whether no window has a redraw or a paste pending.
     pub fn is_idle(&self) -> bool {
         self.entries
             .values()
-            .all(|window| matches!(window.redraw_at, RedrawRequest::Wait))
+            .all(|window| matches!(window.redraw_at, RedrawRequest::Wait) && window.paste.is_none())
     }
 
-    pub fn redraw_at(&self) -> Option<Instant> {
+    /// Returns the next instant at which a window needs to be woken up,
+    /// either to be redrawn, to hide its cursor or to resume its redraws.
+    pub fn wake_at(&self) -> Option<Instant> {
         self.entries
             .values()
-            .filter_map(|window| match window.redraw_at {
-                RedrawRequest::At(at) => Some(at),
-                _ => None,
+            .flat_map(|window| {
+                let redraw_at = match window.redraw_at {
+                    RedrawRequest::At(at) => Some(at),
+                    _ => None,
+                };
+
+                [
+                    redraw_at,
+                    window.cursor_hide_at,
+                    window.redraws_suspended_until,
+                    window.occluded_at(),
+                    window.first_frame_at,
+                ]
+                .into_iter()
+                .flatten()
             })
             .min()
     }
@@ -126,17 +209,22 @@ where
     }
 
     pub fn get_mut_alias(&mut self, surface: &WlSurface) -> Option<(Id, &mut Window<P>)> {
-        let id = self.aliases.get(surface).copied()?;
+        let id = self.aliases.get(surface)?;
 
         Some((id, self.get_mut(id)?))
     }
 
     pub fn remove(&mut self, id: Id) -> Option<Window<P>> {
         let window = self.entries.remove(&id)?;
-        let _ = self.aliases.remove(window.raw.surface());
+        self.aliases.remove(id);
 
         Some(window)
     }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = (Id, Window<P>)> + '_ {
+        self.aliases.clear();
+        std::mem::take(&mut self.entries).into_iter()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -189,6 +277,7 @@ where
     pub qh: QueueHandle<crate::State<P>>,
     pub conn: Connection,
     pub raw: RawWindow,
+    pub layer_settings: Option<layer_shell::Settings>,
     pub state: State<P>,
     pub viewport_version: u64,
     pub mouse_interaction: mouse::Interaction,
@@ -196,10 +285,116 @@ where
     pub renderer: P::Renderer,
     pub redraw_at: RedrawRequest,
     pub pointers: FxHashMap<WlPointer, Rc<ThemedPointer>>,
+    /// The buttons held by each pointer over the window.
+    pub held_buttons: Vec<(WlPointer, u32)>,
     preedit: Option<Preedit<P::Renderer>>,
     ime_state: Option<(Point, input_method::Purpose)>,
+    pub surrounding_text: Option<crate::input_method::SurroundingText>,
+    alpha_modifier: Option<WpAlphaModifierSurfaceV1>,
+    tearing_control: Option<WpTearingControlV1>,
+    /// The pointers confined to the window, with their confinements.
+    pub confined_pointers: Vec<(WlPointer, ZwpConfinedPointerV1)>,
+    /// Whether the keyboard interactivity was raised from `None` to focus
+    /// the window, and must be lowered again once it loses the focus.
+    pub raised_interactivity: bool,
+    /// The paste to perform once the pending events of the window are
+    /// processed.
+    pub paste: Option<Paste>,
+    cursor_auto_hide: Option<Duration>,
+    cursor_hide_at: Option<Instant>,
+    cursor_hidden: bool,
+    redraws_suspended_until: Option<Instant>,
+    redraw_deferred: bool,
+    pub subsurfaces: FxHashMap<subsurface::Id, subsurface::Embedded>,
+    /// The outputs the window has entered, in order. The first one is its
+    /// current output.
+    pub outputs: Vec<WlOutput>,
+    /// The queries waiting for the window to enter an output.
+    pub output_requests: Vec<oneshot::Sender<Option<String>>>,
+    pub present_requests: Vec<oneshot::Sender<()>>,
+    min_redraw_interval: Option<Duration>,
+    last_redraw_at: Option<Instant>,
+    auto_exclusive_zone: bool,
+    input_margin: Option<Padding>,
+    pub shrink_listeners: Vec<mpsc::UnboundedSender<crate::layer_shell::Shrink>>,
+    frame_requested_at: Option<Instant>,
+    first_frame_at: Option<Instant>,
+    is_occluded: bool,
+    pub visibility_listeners: Vec<mpsc::UnboundedSender<bool>>,
+    #[cfg(feature = "dmabuf-feedback")]
+    dmabuf_feedback: Option<DmabufFeedback>,
+    #[cfg(feature = "blur")]
+    blur: Option<OrgKdeKwinBlur>,
+}
+
+/// The dmabuf feedback object of a [`Window`], along with the latest
+/// feedback it received and its listeners.
+#[cfg(feature = "dmabuf-feedback")]
+#[derive(Debug)]
+struct DmabufFeedback {
+    proxy: ZwpLinuxDmabufFeedbackV1,
+    latest: Option<crate::dmabuf::Feedback>,
+    listeners: Vec<mpsc::UnboundedSender<crate::dmabuf::Feedback>>,
+}
+
+/// Removes the pointer from the ones over a window, returning whether it was
+/// the last one.
+fn remove_last<K, V>(pointers: &mut FxHashMap<K, V>, pointer: &K) -> bool
+where
+    K: Eq + std::hash::Hash,
+{
+    pointers.remove(pointer).is_some() && pointers.is_empty()
 }
 
+/// What a frame callback does with the surface of a window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frame {
+    /// Nothing can be presented until the surface is configured with a
+    /// non-zero size, which requests a new frame on its own.
+    Skip,
+    /// A frame is presented, after the compositor surface is reconfigured if
+    /// the viewport changed since it was last configured.
+    Present {
+        /// Whether the compositor surface must be reconfigured first.
+        reconfigure: bool,
+    },
+}
+
+/// Decides what to do with a frame of a surface of the given physical size.
+///
+/// A skipped frame leaves the configured version untouched, so a surface
+/// that was zero-sized is always reconfigured before its first present.
+fn prepare_frame(
+    physical_size: Size<u32>,
+    viewport_version: u64,
+    configured_version: u64,
+) -> Frame {
+    if physical_size.width == 0 || physical_size.height == 0 {
+        return Frame::Skip;
+    }
+
+    Frame::Present {
+        reconfigure: viewport_version != configured_version,
+    }
+}
+
+/// How long a requested frame may take before the window is considered
+/// occluded.
+///
+/// Compositors stop sending frame callbacks to surfaces that are not visible
+/// (e.g. on another workspace), while a visible surface gets one on every
+/// refresh of its output.
+const OCCLUSION_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long the first frame callback of a window may take before the window
+/// is drawn without it.
+///
+/// Compositors may never send a frame callback to a surface that is occluded
+/// from the start (e.g. a background layer opened behind a fullscreen
+/// window), which would otherwise never be drawn until something else wakes
+/// it up.
+const FIRST_FRAME_TIMEOUT: Duration = Duration::from_millis(200);
+
 impl<P> Window<P>
 where
     P: Program,
@@ -209,14 +404,93 @@ where
         self.state.logical_size()
     }
 
+    /// Destroys the [`Window`] in an order the compositor accepts: the
+    /// graphics surface first, then the role object (e.g. the layer surface)
+    /// and, finally, the `wl_surface` itself.
+    pub fn destroy(self) {
+        let Window {
+            surface,
+            raw,
+            alpha_modifier,
+            tearing_control,
+            confined_pointers,
+            subsurfaces,
+            #[cfg(feature = "dmabuf-feedback")]
+            dmabuf_feedback,
+            #[cfg(feature = "blur")]
+            blur,
+            ..
+        } = self;
+
+        #[cfg(feature = "blur")]
+        if let Some(blur) = blur {
+            blur.release();
+        }
+
+        #[cfg(feature = "dmabuf-feedback")]
+        if let Some(dmabuf_feedback) = dmabuf_feedback {
+            dmabuf_feedback.proxy.destroy();
+        }
+
+        for (_, confined_pointer) in confined_pointers {
+            confined_pointer.destroy();
+        }
+
+        if let Some(alpha_modifier) = alpha_modifier {
+            alpha_modifier.destroy();
+        }
+
+        if let Some(tearing_control) = tearing_control {
+            tearing_control.destroy();
+        }
+
+        for (_id, subsurface) in subsurfaces {
+            subsurface.destroy();
+        }
+
+        // The graphics surface may still reference the `wl_surface`.
+        drop(surface);
+
+        // This should be the last handle of the role object. Dropping it
+        // destroys the role object before its `wl_surface`.
+        drop(raw);
+    }
+
+    /// Decides what to do with the frame callback of the [`Window`].
+    pub fn prepare_frame(&self) -> Frame {
+        prepare_frame(
+            self.state.physical_size(),
+            self.state.viewport_version(),
+            self.viewport_version,
+        )
+    }
+
     pub fn request_redraw(&mut self, redraw_request: RedrawRequest) {
+        if self.redraws_suspended_until.is_some() {
+            self.redraw_deferred |= !matches!(redraw_request, RedrawRequest::Wait);
+            return;
+        }
+
         if let RedrawRequest::NextFrame = self.redraw_at {
             return;
         }
 
+        let redraw_request = self.throttle(redraw_request);
+
+        // A throttled request must not postpone an earlier one.
+        if let (RedrawRequest::At(current), RedrawRequest::At(new)) =
+            (self.redraw_at, redraw_request)
+            && self.min_redraw_interval.is_some()
+            && current < new
+        {
+            return;
+        }
+
         self.redraw_at = redraw_request;
 
         if let RedrawRequest::NextFrame = redraw_request {
+            self.frame_requested_at = Some(Instant::now());
+
             let _ = self
                 .raw
                 .surface()
@@ -225,6 +499,175 @@ where
         }
     }
 
+    /// Delays the [`RedrawRequest`] until the minimum redraw interval of the
+    /// [`Window`] has elapsed since its last redraw.
+    fn throttle(&self, redraw_request: RedrawRequest) -> RedrawRequest {
+        let (Some(interval), Some(last_redraw_at)) =
+            (self.min_redraw_interval, self.last_redraw_at)
+        else {
+            return redraw_request;
+        };
+
+        let earliest = last_redraw_at + interval;
+
+        match redraw_request {
+            RedrawRequest::NextFrame if Instant::now() < earliest => RedrawRequest::At(earliest),
+            RedrawRequest::At(at) if at < earliest => RedrawRequest::At(earliest),
+            redraw_request => redraw_request,
+        }
+    }
+
+    /// Sets the minimum interval between two redraws of the [`Window`], or
+    /// removes it.
+    pub fn set_min_redraw_interval(&mut self, interval: Option<Duration>) {
+        self.min_redraw_interval = interval;
+    }
+
+    /// Records that the [`Window`] is being redrawn at the given instant.
+    pub fn redrawn_at(&mut self, now: Instant) {
+        self.last_redraw_at = Some(now);
+    }
+
+    /// Returns the instant at which the [`Window`] is considered occluded,
+    /// if a frame is pending and its visibility is observed.
+    fn occluded_at(&self) -> Option<Instant> {
+        if self.is_occluded || self.visibility_listeners.is_empty() {
+            return None;
+        }
+
+        self.frame_requested_at
+            .map(|requested_at| requested_at + OCCLUSION_TIMEOUT)
+    }
+
+    /// Records the arrival of a frame callback, which makes the [`Window`]
+    /// visible again if it was occluded.
+    pub fn frame_arrived(&mut self) {
+        self.frame_requested_at = None;
+        self.first_frame_at = None;
+
+        if self.is_occluded {
+            self.is_occluded = false;
+            self.notify_visibility();
+        }
+    }
+
+    /// Returns whether the first frame callback of the [`Window`] has not
+    /// arrived in time, in which case it must be drawn without it.
+    pub fn is_first_frame_overdue(&self, now: Instant) -> bool {
+        self.first_frame_at
+            .is_some_and(|first_frame_at| first_frame_at <= now)
+    }
+
+    /// Returns whether the frames of the [`Window`] are throttled, whether its
+    /// visibility is observed or not.
+    pub fn is_throttled(&self, now: Instant) -> bool {
+        self.is_occluded
+            || self
+                .frame_requested_at
+                .is_some_and(|requested_at| requested_at + OCCLUSION_TIMEOUT <= now)
+    }
+
+    /// Marks the [`Window`] as occluded if its pending frame has not arrived
+    /// in time.
+    pub fn detect_occlusion(&mut self, now: Instant) {
+        if self
+            .occluded_at()
+            .is_some_and(|occluded_at| occluded_at <= now)
+        {
+            self.is_occluded = true;
+            self.notify_visibility();
+        }
+    }
+
+    /// Subscribes the listener to the dmabuf feedback of the [`Window`],
+    /// requesting it from the compositor the first time.
+    ///
+    /// The listener is dropped if the compositor does not support feedback
+    /// per surface.
+    #[cfg(feature = "dmabuf-feedback")]
+    pub fn listen_dmabuf_feedback(
+        &mut self,
+        dmabuf: &sctk::dmabuf::DmabufState,
+        listener: mpsc::UnboundedSender<crate::dmabuf::Feedback>,
+    ) {
+        let dmabuf_feedback = match &mut self.dmabuf_feedback {
+            Some(dmabuf_feedback) => dmabuf_feedback,
+            None => {
+                let Ok(proxy) = dmabuf.get_surface_feedback(self.raw.surface(), &self.qh) else {
+                    return;
+                };
+
+                self.dmabuf_feedback.insert(DmabufFeedback {
+                    proxy,
+                    latest: None,
+                    listeners: Vec::new(),
+                })
+            }
+        };
+
+        if let Some(latest) = &dmabuf_feedback.latest
+            && listener.unbounded_send(latest.clone()).is_err()
+        {
+            return;
+        }
+
+        dmabuf_feedback.listeners.push(listener);
+    }
+
+    #[cfg(feature = "dmabuf-feedback")]
+    pub fn has_dmabuf_feedback(&self, proxy: &ZwpLinuxDmabufFeedbackV1) -> bool {
+        self.dmabuf_feedback
+            .as_ref()
+            .is_some_and(|dmabuf_feedback| dmabuf_feedback.proxy == *proxy)
+    }
+
+    #[cfg(feature = "dmabuf-feedback")]
+    pub fn notify_dmabuf_feedback(&mut self, feedback: crate::dmabuf::Feedback) {
+        let Some(dmabuf_feedback) = &mut self.dmabuf_feedback else {
+            return;
+        };
+
+        dmabuf_feedback
+            .listeners
+            .retain(|listener| listener.unbounded_send(feedback.clone()).is_ok());
+        dmabuf_feedback.latest = Some(feedback);
+    }
+
+    fn notify_visibility(&mut self) {
+        let is_visible = !self.is_occluded;
+
+        self.visibility_listeners
+            .retain(|listener| listener.unbounded_send(is_visible).is_ok());
+    }
+
+    pub fn redraws_suspended(&self) -> bool {
+        self.redraws_suspended_until.is_some()
+    }
+
+    /// Defers every redraw of the [`Window`] until [`Window::resume_redraws`]
+    /// is called or the timeout elapses, whichever comes first.
+    pub fn suspend_redraws(&mut self, timeout: Duration) {
+        self.redraws_suspended_until = Some(Instant::now() + timeout);
+    }
+
+    /// Resumes the redraws of the [`Window`], drawing a single frame if any
+    /// redraw was deferred while they were suspended.
+    pub fn resume_redraws(&mut self) {
+        if self.redraws_suspended_until.take().is_some() && mem::take(&mut self.redraw_deferred) {
+            self.request_redraw(RedrawRequest::NextFrame);
+        }
+    }
+
+    /// Resumes the redraws of the [`Window`] if their suspension timed out.
+    pub fn resume_expired_redraws(&mut self, now: Instant) {
+        if self
+            .redraws_suspended_until
+            .is_some_and(|suspended_until| suspended_until <= now)
+        {
+            self.resume_redraws();
+        }
+    }
+
     pub fn request_input_method(&mut self, input_method: InputMethod) {
         match input_method {
             InputMethod::Disabled => {
@@ -259,15 +702,450 @@ where
         }
     }
 
+    pub fn set_keyboard_interactivity(
+        &mut self,
+        keyboard_interactivity: layer_shell::KeyboardInteractivity,
+    ) {
+        let (Some(settings), RawWindow::Layer(_, layer_surface)) =
+            (self.layer_settings.as_mut(), &self.raw)
+        else {
+            return;
+        };
+
+        layer_surface.set_keyboard_interactivity(conversion::layer_shell::keyboard_interactivity(
+            keyboard_interactivity,
+        ));
+        layer_surface.commit();
+
+        settings.keyboard_interactivity = keyboard_interactivity;
+    }
+
+    pub fn set_exclusive_zone(&mut self, exclusive_zone: crate::layer_shell::ExclusiveZone) {
+        let (Some(settings), RawWindow::Layer(_, layer_surface)) =
+            (self.layer_settings.as_mut(), &self.raw)
+        else {
+            return;
+        };
+
+        let zone = match exclusive_zone {
+            crate::layer_shell::ExclusiveZone::Fixed(zone) => zone,
+            crate::layer_shell::ExclusiveZone::Auto => {
+                conversion::layer_shell::auto_exclusive_zone(
+                    settings.anchor,
+                    self.state.surface_size(),
+                )
+            }
+        };
+
+        self.auto_exclusive_zone = exclusive_zone == crate::layer_shell::ExclusiveZone::Auto;
+
+        layer_surface.set_exclusive_zone(zone);
+        layer_surface.commit();
+
+        settings.exclusive_zone = zone;
+        self.request_redraw(RedrawRequest::NextFrame);
+    }
+
+    /// Applies the given changes to the layer surface of the [`Window`] and
+    /// commits them at once.
+    pub fn apply_layer_changes(&mut self, changes: crate::layer_shell::Changes) {
+        let (Some(settings), RawWindow::Layer(_, layer_surface)) =
+            (self.layer_settings.as_mut(), &self.raw)
+        else {
+            return;
+        };
+
+        if let Some(anchor) = changes.anchor {
+            layer_surface.set_anchor(conversion::layer_shell::anchor(anchor));
+            settings.anchor = anchor;
+        }
+
+        if let Some(size) = changes.size {
+            layer_surface.set_size(size.width, size.height);
+            settings.size = size;
+        }
+
+        if let Some(margin) = changes.margin {
+            layer_surface.set_margin(margin.top, margin.right, margin.bottom, margin.left);
+            settings.margin = margin;
+        }
+
+        if let Some(keyboard_interactivity) = changes.keyboard_interactivity {
+            layer_surface.set_keyboard_interactivity(
+                conversion::layer_shell::keyboard_interactivity(keyboard_interactivity),
+            );
+            settings.keyboard_interactivity = keyboard_interactivity;
+        }
+
+        if let Some(exclusive_zone) = changes.exclusive_zone {
+            self.auto_exclusive_zone = exclusive_zone == crate::layer_shell::ExclusiveZone::Auto;
+
+            settings.exclusive_zone = match exclusive_zone {
+                crate::layer_shell::ExclusiveZone::Fixed(zone) => zone,
+                crate::layer_shell::ExclusiveZone::Auto => {
+                    conversion::layer_shell::auto_exclusive_zone(
+                        settings.anchor,
+                        self.state.surface_size(),
+                    )
+                }
+            };
+        } else if changes.anchor.is_some() && self.auto_exclusive_zone {
+            // The zone follows the edge the surface is anchored to.
+            settings.exclusive_zone = conversion::layer_shell::auto_exclusive_zone(
+                settings.anchor,
+                self.state.surface_size(),
+            );
+        }
+
+        layer_surface.set_exclusive_zone(settings.exclusive_zone);
+        layer_surface.commit();
+
+        self.request_redraw(RedrawRequest::NextFrame);
+    }
+
+    /// Notifies the listeners of the [`Window`] if a configure made it
+    /// smaller along any dimension.
+    pub fn notify_shrink(&mut self, serial: u32, previous: Size<u32>, new: Size<u32>) {
+        if new.width >= previous.width && new.height >= previous.height {
+            return;
+        }
+
+        let shrink = crate::layer_shell::Shrink {
+            serial,
+            previous,
+            new,
+        };
+
+        self.shrink_listeners
+            .retain(|listener| listener.unbounded_send(shrink).is_ok());
+    }
+
+    /// Sets the margin around the input region of the [`Window`], applying
+    /// it on the next commit.
+    pub fn set_input_margin(&mut self, compositor: &CompositorState, margin: Option<Padding>) {
+        self.input_margin = margin;
+
+        if margin.is_some() {
+            self.update_input_region(compositor);
+        } else {
+            self.raw.surface().set_input_region(None);
+        }
+
+        self.request_redraw(RedrawRequest::NextFrame);
+    }
+
+    /// Updates the input region of the [`Window`] after the surface was
+    /// resized, if it has a margin.
+    ///
+    /// The region is committed along with the next frame.
+    pub fn update_input_region(&self, compositor: &CompositorState) {
+        let Some(margin) = self.input_margin else {
+            return;
+        };
+
+        let Ok(region) = Region::new(compositor) else {
+            return;
+        };
+
+        let size = self.state.surface_size();
+        let width = size.width as f32 - margin.left - margin.right;
+        let height = size.height as f32 - margin.top - margin.bottom;
+
+        region.add(
+            margin.left.round() as i32,
+            margin.top.round() as i32,
+            width.max(0.0).round() as i32,
+            height.max(0.0).round() as i32,
+        );
+
+        // The region is copied by the surface, so it can be destroyed right
+        // away.
+        self.raw
+            .surface()
+            .set_input_region(Some(region.wl_region()));
+    }
+
+    /// Updates an automatic exclusive zone after the surface was resized.
+    ///
+    /// The zone is committed along with the next frame.
+    pub fn update_auto_exclusive_zone(&mut self) {
+        let (true, Some(settings), RawWindow::Layer(_, layer_surface)) = (
+            self.auto_exclusive_zone,
+            self.layer_settings.as_mut(),
+            &self.raw,
+        ) else {
+            return;
+        };
+
+        let zone = conversion::layer_shell::auto_exclusive_zone(
+            settings.anchor,
+            self.state.surface_size(),
+        );
+
+        if zone != settings.exclusive_zone {
+            layer_surface.set_exclusive_zone(zone);
+            settings.exclusive_zone = zone;
+        }
+    }
+
+    pub fn set_margin(&mut self, margin: crate::core::Padding<i32>) {
+        let (Some(settings), RawWindow::Layer(_, layer_surface)) =
+            (self.layer_settings.as_mut(), &self.raw)
+        else {
+            return;
+        };
+
+        if settings.margin == margin {
+            return;
+        }
+
+        layer_surface.set_margin(margin.top, margin.right, margin.bottom, margin.left);
+
+        // A pending frame commits the margin along with its buffer.
+        if !matches!(self.redraw_at, RedrawRequest::NextFrame) {
+            layer_surface.commit();
+        }
+
+        settings.margin = margin;
+    }
+
+    pub fn set_layer(&mut self, layer: layer_shell::Layer) {
+        let (Some(settings), RawWindow::Layer(_, layer_surface)) =
+            (self.layer_settings.as_mut(), &self.raw)
+        else {
+            return;
+        };
+
+        layer_surface.set_layer(conversion::layer_shell::layer(layer));
+        layer_surface.commit();
+
+        settings.layer = layer;
+        self.request_redraw(RedrawRequest::NextFrame);
+    }
+
+    /// Sets the buffer scale of the window, returning whether it changed.
+    pub fn set_scale(&mut self, scale: u32) -> bool {
+        if scale == self.state.surface_scale() {
+            return false;
+        }
+
+        self.raw.surface().set_buffer_scale(scale as i32);
+        self.state.rescale(scale);
+        self.request_redraw(RedrawRequest::NextFrame);
+
+        true
+    }
+
+    pub fn set_alpha(&mut self, alpha_modifier: &WpAlphaModifierV1, alpha: f32) {
+        let surface = self.raw.surface();
+        let alpha_modifier = self
+            .alpha_modifier
+            .get_or_insert_with(|| alpha_modifier.get_surface(surface, &self.qh, ()));
+
+        alpha_modifier
+            .set_multiplier((f64::from(alpha.clamp(0.0, 1.0)) * f64::from(u32::MAX)) as u32);
+        surface.commit();
+    }
+
+    #[cfg(feature = "blur")]
+    pub fn set_blur(&mut self, blur_manager: &OrgKdeKwinBlurManager, blur: bool) {
+        let surface = self.raw.surface();
+
+        match (blur, self.blur.take()) {
+            (true, None) => {
+                let blur = blur_manager.create(surface, &self.qh, ());
+
+                // Without a region, the whole surface is blurred.
+                blur.commit();
+                self.blur = Some(blur);
+            }
+            (false, Some(blur)) => {
+                blur.release();
+                blur_manager.unset(surface);
+            }
+            (_, blur) => {
+                self.blur = blur;
+                return;
+            }
+        }
+
+        surface.commit();
+    }
+
+    /// Removes a pointer that is no longer over the [`Window`], returning
+    /// whether the cursor left it.
+    ///
+    /// The pointers of different seats (e.g. a mouse and a tablet) share the
+    /// cursor of the window, which only leaves once none of them is over it.
+    pub fn remove_pointer(&mut self, pointer: &WlPointer) -> bool {
+        let has_left = remove_last(&mut self.pointers, pointer);
+
+        if has_left {
+            self.state.update_cursor(None);
+        }
+
+        has_left
+    }
+
+    /// Forgets the buttons held by the given pointer, returning them so
+    /// their release can be reported.
+    ///
+    /// The drag they held ends, so the pointer is released as well.
+    pub fn release_buttons(&mut self, pointer: &WlPointer) -> Vec<mouse::Button> {
+        self.release_pointer(pointer);
+
+        let mut released = Vec::new();
+
+        self.held_buttons.retain(|(held, button)| {
+            if held != pointer {
+                return true;
+            }
+
+            released.push(conversion::mouse::button(*button));
+            false
+        });
+
+        released
+    }
+
+    pub fn set_presentation_hint(
+        &mut self,
+        tearing_control_manager: &WpTearingControlManagerV1,
+        hint: crate::surface::PresentationHint,
+    ) {
+        let surface = self.raw.surface();
+        let tearing_control = self.tearing_control.get_or_insert_with(|| {
+            tearing_control_manager.get_tearing_control(surface, &self.qh, ())
+        });
+
+        tearing_control.set_presentation_hint(match hint {
+            crate::surface::PresentationHint::Vsync => {
+                wp_tearing_control_v1::PresentationHint::Vsync
+            }
+            crate::surface::PresentationHint::Async => {
+                wp_tearing_control_v1::PresentationHint::Async
+            }
+        });
+        surface.commit();
+    }
+
+    /// Releases the pointers confined to the window.
+    pub fn release_pointers(&mut self) {
+        for (_, confined_pointer) in self.confined_pointers.drain(..) {
+            confined_pointer.destroy();
+        }
+    }
+
+    /// Releases the given pointer, if it is confined to the window.
+    pub fn release_pointer(&mut self, pointer: &WlPointer) {
+        self.confined_pointers
+            .retain(|(confined, confined_pointer)| {
+                if confined != pointer {
+                    return true;
+                }
+
+                confined_pointer.destroy();
+                false
+            });
+    }
+
     pub fn update_mouse(&mut self, interaction: mouse::Interaction) {
-        for themed_pointer in self.pointers.values() {
-            let _ =
-                themed_pointer.set_cursor(&self.conn, crate::conversion::mouse::icon(interaction));
+        if !self.cursor_hidden {
+            for themed_pointer in self.pointers.values() {
+                let _ = themed_pointer
+                    .set_cursor(&self.conn, crate::conversion::mouse::icon(interaction));
+            }
         }
 
         self.mouse_interaction = interaction;
     }
 
+    /// Sets the cursor of the pointers over the window again, so it is
+    /// loaded at the current scale of their cursor surfaces.
+    ///
+    /// Pointers using the cursor shape protocol only set their shape again,
+    /// since the compositor scales their cursors itself.
+    pub fn refresh_cursor(&mut self) {
+        self.update_mouse(self.mouse_interaction);
+    }
+
+    /// Hides the cursor after it has not moved over the window for the given
+    /// timeout, or disables auto-hiding if `None`.
+    pub fn set_cursor_auto_hide(&mut self, timeout: Option<Duration>) {
+        self.cursor_auto_hide = timeout;
+        self.show_cursor();
+    }
+
+    /// Shows the cursor if it was hidden and restarts the auto-hide timeout.
+    pub fn show_cursor(&mut self) {
+        if self.cursor_hidden {
+            self.cursor_hidden = false;
+            self.update_mouse(self.mouse_interaction);
+        }
+
+        self.cursor_hide_at = self
+            .cursor_auto_hide
+            .map(|timeout| Instant::now() + timeout);
+    }
+
+    /// Hides the cursor if its auto-hide timeout has elapsed.
+    pub fn hide_idle_cursor(&mut self, now: Instant) {
+        if self.cursor_hide_at.is_some_and(|hide_at| hide_at <= now) {
+            self.cursor_hide_at = None;
+            self.cursor_hidden = true;
+
+            for themed_pointer in self.pointers.values() {
+                let _ = themed_pointer.hide_cursor();
+            }
+        }
+    }
+
+    /// Returns the [`text_input::Request`] of the [`Window`], if it has
+    /// enabled the input method.
+    pub fn ime_request(&self) -> Option<text_input::Request> {
+        let (position, purpose) = self.ime_state?;
+        let scale_factor = self.state.scale_factor() as f32;
+
+        // The input method dialog is placed at `position`, right below the
+        // caret, which is about as tall as the text.
+        let height = f32::from(text::Renderer::default_size(&self.renderer));
+
+        Some(text_input::Request {
+            cursor: Rectangle {
+                x: (position.x * scale_factor).round() as i32,
+                y: ((position.y - height) * scale_factor).round() as i32,
+                width: 1,
+                height: (height * scale_factor).round() as i32,
+            },
+            purpose,
+            surrounding_text: self.surrounding_text.clone(),
+        })
+    }
+
+    /// Replaces the graphics surface and the renderer of the [`Window`] with
+    /// new ones created by the given compositor.
+    pub fn recreate_graphics(
+        &mut self,
+        compositor: &mut <P::Renderer as compositor::Default>::Compositor,
+    ) {
+        let size = self.state.buffer_size();
+
+        // The previous surface is dropped before the new one is configured,
+        // since a `wl_surface` only presents the buffers of one swapchain.
+        self.surface = compositor.create_surface(self.raw.clone(), 0, 0);
+        compositor.configure_surface(&mut self.surface, size.width, size.height);
+
+        self.renderer = compositor.create_renderer();
+        self.request_redraw(RedrawRequest::NextFrame);
+    }
+
+    /// Draws the preedit overlay on top of the user interface.
+    ///
+    /// The layers of the renderer are rebuilt on every frame, so the overlay
+    /// must be drawn on every frame as well, but it never requests a redraw
+    /// on its own. Its paragraph is only shaped again when its text changes,
+    /// and an unchanged overlay produces no damage for renderers that track
+    /// it (e.g. `tiny-skia`).
     pub fn draw_preedit(&mut self) {
         if let Some(preedit) = &self.preedit {
             preedit.draw(
@@ -279,22 +1157,16 @@ where
         }
     }
 
+    // The text inputs of the seats are synchronized with the IME state of
+    // the window they are focused on after every redraw.
     fn enable_ime(&mut self, position: Point, purpose: input_method::Purpose) {
-        if self.ime_state.is_none() {
-            // TODO: enable ime
-        }
-
-        if self.ime_state != Some((position, purpose)) {
-            // TODO: configure position and purpose
-
-            self.ime_state = Some((position, purpose));
-        }
+        self.ime_state = Some((position, purpose));
     }
 
     fn disable_ime(&mut self) {
-        if self.ime_state.is_some() {
-            // TOOD: enable ime
-            self.ime_state = None;
+        // The surrounding text belonged to the widget that was unfocused.
+        if self.ime_state.take().is_some() {
+            self.surrounding_text = None;
         }
 
         self.preedit = None;
@@ -308,6 +1180,7 @@ where
     position: Point,
     content: Renderer::Paragraph,
     spans: Vec<text::Span<'static, (), Renderer::Font>>,
+    size: Pixels,
 }
 
 impl<Renderer> Preedit<Renderer>
@@ -319,6 +1192,7 @@ where
             position: Point::ORIGIN,
             spans: Vec::new(),
             content: Renderer::Paragraph::default(),
+            size: Pixels::ZERO,
         }
     }
 
@@ -347,13 +1221,16 @@ where
             _ => vec![text::Span::new(&preedit.content)],
         };
 
-        if spans != self.spans.as_slice() {
+        let size = preedit.text_size.unwrap_or_else(|| renderer.default_size());
+
+        if spans != self.spans.as_slice() || size != self.size {
             use text::Paragraph as _;
 
+            self.size = size;
             self.content = Renderer::Paragraph::with_spans(Text {
                 content: &spans,
                 bounds: Size::INFINITY,
-                size: preedit.text_size.unwrap_or_else(|| renderer.default_size()),
+                size,
                 line_height: text::LineHeight::default(),
                 font: renderer.default_font(),
                 align_x: text::Alignment::Default,
@@ -426,3 +1303,53 @@ where
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_leaves_with_the_last_pointer() {
+        let mut pointers = FxHashMap::default();
+        let _ = pointers.insert("mouse", ());
+        let _ = pointers.insert("tablet", ());
+
+        assert!(!remove_last(&mut pointers, &"mouse"));
+        assert!(remove_last(&mut pointers, &"tablet"));
+
+        // A pointer removed along with its seat after it left the window.
+        assert!(!remove_last(&mut pointers, &"tablet"));
+        assert!(pointers.is_empty());
+    }
+
+    #[test]
+    fn zero_sized_surface_presents_once_configured() {
+        // The surface is created from a `configure(0, 0)`.
+        let configured_version = 0;
+        assert_eq!(
+            prepare_frame(Size::new(0, 0), 0, configured_version),
+            Frame::Skip
+        );
+
+        // A `configure(800, 600)` changes the viewport, and the next frame
+        // reconfigures the compositor surface before presenting.
+        assert_eq!(
+            prepare_frame(Size::new(800, 600), 1, configured_version),
+            Frame::Present { reconfigure: true }
+        );
+        assert_eq!(
+            prepare_frame(Size::new(800, 600), 1, 1),
+            Frame::Present { reconfigure: false }
+        );
+    }
+}
+
+/// Text to paste into the focusable widget at a position, in logical
+/// coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Paste {
+    /// A middle-click that pastes the primary selection.
+    Primary(Point),
+    /// Text dropped onto the window.
+    Dropped(Point, String),
+}
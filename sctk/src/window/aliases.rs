@@ -0,0 +1,98 @@
+use std::hash::Hash;
+
+use rustc_hash::FxHashMap;
+
+use crate::core::window::Id;
+
+/// The one-to-one mapping between the surfaces of the windows and their
+/// [`Id`].
+///
+/// Input events only carry the surface they target, so a stale entry would
+/// route them to a window that no longer exists, or to the wrong one.
+#[derive(Debug)]
+pub struct Aliases<S> {
+    ids: FxHashMap<S, Id>,
+    surfaces: FxHashMap<Id, S>,
+}
+
+impl<S> Aliases<S>
+where
+    S: Clone + Eq + Hash,
+{
+    pub fn new() -> Self {
+        Self {
+            ids: FxHashMap::default(),
+            surfaces: FxHashMap::default(),
+        }
+    }
+
+    /// Maps the surface to the [`Id`], replacing any previous mapping of
+    /// either of them.
+    pub fn insert(&mut self, surface: S, id: Id) {
+        if let Some(previous) = self.surfaces.insert(id, surface.clone()) {
+            let _ = self.ids.remove(&previous);
+        }
+
+        if let Some(previous) = self.ids.insert(surface, id)
+            && previous != id
+        {
+            let _ = self.surfaces.remove(&previous);
+        }
+
+        debug_assert_eq!(self.ids.len(), self.surfaces.len());
+    }
+
+    pub fn get(&self, surface: &S) -> Option<Id> {
+        self.ids.get(surface).copied()
+    }
+
+    /// Removes the mapping of the [`Id`], whatever its surface is.
+    pub fn remove(&mut self, id: Id) {
+        if let Some(surface) = self.surfaces.remove(&id) {
+            let _ = self.ids.remove(&surface);
+        }
+
+        debug_assert_eq!(self.ids.len(), self.surfaces.len());
+    }
+
+    pub fn clear(&mut self) {
+        self.ids.clear();
+        self.surfaces.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reused_id_forgets_its_previous_surface() {
+        let mut aliases = Aliases::new();
+        let id = Id::unique();
+
+        aliases.insert("closed", id);
+        aliases.remove(id);
+        assert_eq!(aliases.get(&"closed"), None);
+
+        aliases.insert("reopened", id);
+        aliases.insert("replaced", id);
+
+        assert_eq!(aliases.get(&"reopened"), None);
+        assert_eq!(aliases.get(&"replaced"), Some(id));
+    }
+
+    #[test]
+    fn reused_surface_forgets_its_previous_id() {
+        let mut aliases = Aliases::new();
+        let (first, second) = (Id::unique(), Id::unique());
+
+        aliases.insert("surface", first);
+        aliases.insert("surface", second);
+        assert_eq!(aliases.get(&"surface"), Some(second));
+
+        // The first window no longer owns the surface, so its removal must
+        // not unmap it from the second one.
+        aliases.remove(first);
+        assert_eq!(aliases.get(&"surface"), Some(second));
+    }
+}
@@ -15,11 +15,16 @@ where
 {
     title: String,
     scale_factor: f64,
+    surface: Surface,
     viewport: Viewport,
     viewport_version: u64,
+    snap_to_pixels: bool,
     cursor_position: Option<Point<f32>>,
     modifiers: sctk::seat::keyboard::Modifiers,
     theme: P::Theme,
+    theme_override: bool,
+    scale_factor_override: Option<f64>,
+    opacity: f32,
     style: theme::Style,
 }
 
@@ -31,9 +36,14 @@ where
         f.debug_struct("multi_window::State")
             .field("title", &self.title)
             .field("scale_factor", &self.scale_factor)
+            .field("surface", &self.surface)
             .field("viewport", &self.viewport)
             .field("viewport_version", &self.viewport_version)
+            .field("snap_to_pixels", &self.snap_to_pixels)
             .field("cursor_position", &self.cursor_position)
+            .field("theme_override", &self.theme_override)
+            .field("scale_factor_override", &self.scale_factor_override)
+            .field("opacity", &self.opacity)
             .field("style", &self.style)
             .finish()
     }
@@ -43,27 +53,35 @@ impl<P: Program> State<P>
 where
     P::Theme: theme::Base,
 {
-    /// Creates a new [`State`] for the provided [`Program`]'s `window`.
+    /// Creates a new [`State`] for the provided [`Program`]'s `window`, with
+    /// the surface size given in surface-local coordinates.
     pub fn new(
         program: &program::Instance<P>,
         window_id: window::Id,
         surface_size: Size<u32>,
+        snap_to_pixels: bool,
     ) -> Self {
         let title = program.title(window_id);
         let scale_factor = program.scale_factor(window_id);
         let theme = program.theme(window_id);
         let style = program.style(&theme);
 
-        let viewport = Viewport::with_physical_size(surface_size, scale_factor);
+        let surface = Surface::new(surface_size);
+        let viewport = viewport(surface, scale_factor, snap_to_pixels);
 
         Self {
             title,
             scale_factor,
+            surface,
             viewport,
             viewport_version: 0,
+            snap_to_pixels,
             cursor_position: None,
             modifiers: sctk::seat::keyboard::Modifiers::default(),
             theme,
+            theme_override: false,
+            scale_factor_override: None,
+            opacity: 1.0,
             style,
         }
     }
@@ -81,6 +99,9 @@ where
     }
 
     /// Returns the physical [`Size`] of the [`Viewport`] of the [`State`].
+    ///
+    /// If the [`State`] snaps to pixels, this may be slightly smaller than
+    /// the [`buffer_size`](Self::buffer_size).
     pub fn physical_size(&self) -> Size<u32> {
         self.viewport.physical_size()
     }
@@ -90,10 +111,31 @@ where
         self.viewport.logical_size()
     }
 
+    /// Returns the size of the buffers of the surface, in physical pixels.
+    ///
+    /// A `wl_surface` requires its buffers to be a multiple of its scale, so
+    /// they keep the whole size of the surface even if the [`Viewport`] is
+    /// snapped to pixels.
+    pub fn buffer_size(&self) -> Size<u32> {
+        self.surface.physical_size()
+    }
+
+    /// Returns the scale factor of the [`Program`], which excludes the scale
+    /// of the surface.
     pub fn scale_factor(&self) -> f64 {
         self.scale_factor
     }
 
+    /// Returns the size of the surface, in surface-local coordinates.
+    pub fn surface_size(&self) -> Size<u32> {
+        self.surface.size
+    }
+
+    /// Returns the integer scale of the surface.
+    pub fn surface_scale(&self) -> u32 {
+        self.surface.scale
+    }
+
     /// Returns the current cursor position of the [`State`].
     pub fn cursor(&self) -> mouse::Cursor {
         self.cursor_position
@@ -117,7 +159,24 @@ where
 
     /// Returns the current background [`Color`] of the [`State`].
     pub fn background_color(&self) -> Color {
-        self.style.background_color
+        self.style.background_color.scale_alpha(self.opacity)
+    }
+
+    /// Sets the opacity of the background of the window, returning whether
+    /// it changed.
+    ///
+    /// This is the fallback of compositors that cannot change the opacity
+    /// of the whole surface.
+    pub fn set_opacity(&mut self, opacity: f32) -> bool {
+        let opacity = opacity.clamp(0.0, 1.0);
+
+        if opacity == self.opacity {
+            return false;
+        }
+
+        self.opacity = opacity;
+
+        true
     }
 
     /// Returns the current text [`Color`] of the [`State`].
@@ -133,9 +192,68 @@ where
         self.modifiers = modifiers;
     }
 
-    pub fn resize(&mut self, physical_size: Size<u32>) {
-        self.viewport = Viewport::with_physical_size(physical_size, self.scale_factor);
-        let _ = self.viewport_version.wrapping_add(1);
+    /// Overrides the theme of the [`State`], or clears the override and
+    /// falls back to the theme of the [`Program`].
+    pub fn override_theme(
+        &mut self,
+        program: &program::Instance<P>,
+        window_id: window::Id,
+        theme: Option<P::Theme>,
+    ) {
+        self.theme_override = theme.is_some();
+        self.theme = theme.unwrap_or_else(|| program.theme(window_id));
+        self.style = program.style(&self.theme);
+    }
+
+    /// Overrides the scale factor of the [`State`], or clears the override
+    /// and falls back to the scale factor of the [`Program`].
+    ///
+    /// Returns whether the scale factor changed.
+    pub fn override_scale_factor(
+        &mut self,
+        program: &program::Instance<P>,
+        window_id: window::Id,
+        scale_factor: Option<f64>,
+    ) -> bool {
+        self.scale_factor_override = scale_factor;
+
+        let new_scale_factor = scale_factor.unwrap_or_else(|| program.scale_factor(window_id));
+
+        if self.scale_factor == new_scale_factor {
+            return false;
+        }
+
+        self.scale_factor = new_scale_factor;
+        self.update_viewport();
+
+        true
+    }
+
+    /// Resizes the [`State`] to the given surface size, in surface-local
+    /// coordinates.
+    ///
+    /// Returns whether the size actually changed; compositors also send
+    /// configures for state changes that keep the same size.
+    pub fn resize(&mut self, surface_size: Size<u32>) -> bool {
+        let resized = self.surface.resize(surface_size);
+
+        if resized {
+            self.update_viewport();
+        }
+
+        resized
+    }
+
+    /// Changes the integer scale of the surface, which the buffers are
+    /// rendered at.
+    pub fn rescale(&mut self, surface_scale: u32) {
+        self.surface.scale = surface_scale.max(1);
+        self.update_viewport();
+    }
+
+    fn update_viewport(&mut self) {
+        self.viewport = viewport(self.surface, self.scale_factor, self.snap_to_pixels);
+        self.viewport_version = self.viewport_version.wrapping_add(1);
     }
 
     /// Synchronizes the [`State`] with its [`Program`] and its respective
@@ -143,27 +261,123 @@ where
     ///
     /// Normally, a [`Program`] should be synchronized with its [`State`]
     /// and window after calling [`State::update`].
-    pub fn synchronize(&mut self, program: &program::Instance<P>, window_id: window::Id) {
+    ///
+    /// Returns whether the title, the scale factor or the appearance of the
+    /// window changed.
+    pub fn synchronize(&mut self, program: &program::Instance<P>, window_id: window::Id) -> bool {
+        let mut changed = false;
+
         // Update window title
         let new_title = program.title(window_id);
 
         if self.title != new_title {
             // TODO: set title
             self.title = new_title;
+            changed = true;
         }
 
-        let new_scale_factor = program.scale_factor(window_id);
+        let new_scale_factor = self
+            .scale_factor_override
+            .unwrap_or_else(|| program.scale_factor(window_id));
 
         if self.scale_factor != new_scale_factor {
-            self.viewport =
-                Viewport::with_physical_size(self.viewport.physical_size(), new_scale_factor);
-            self.viewport_version = self.viewport_version.wrapping_add(1);
-
             self.scale_factor = new_scale_factor;
+            self.update_viewport();
+            changed = true;
         }
 
         // Update theme and appearance
-        self.theme = program.theme(window_id);
-        self.style = program.style(&self.theme);
+        let palette = theme::Base::palette(&self.theme);
+
+        if !self.theme_override {
+            self.theme = program.theme(window_id);
+        }
+
+        let style = program.style(&self.theme);
+
+        changed |= self.style != style || theme::Base::palette(&self.theme) != palette;
+        self.style = style;
+
+        changed
+    }
+}
+
+/// Creates the [`Viewport`] of a [`Surface`] with the given scale factor of
+/// the [`Program`].
+///
+/// If `snap_to_pixels` is set, the logical size is rounded down to whole
+/// logical pixels and the physical size becomes that size scaled and rounded
+/// to whole device pixels.
+fn viewport(surface: Surface, scale_factor: f64, snap_to_pixels: bool) -> Viewport {
+    let scale = scale_factor * f64::from(surface.scale);
+    let physical_size = surface.physical_size();
+
+    if !snap_to_pixels {
+        return Viewport::with_physical_size(physical_size, scale);
+    }
+
+    let snap = |length: u32| ((f64::from(length) / scale).floor() * scale).round() as u32;
+
+    Viewport::with_physical_size(
+        Size::new(snap(physical_size.width), snap(physical_size.height)),
+        scale,
+    )
+}
+
+/// The size of a surface, in surface-local coordinates, and its integer scale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Surface {
+    size: Size<u32>,
+    scale: u32,
+}
+
+impl Surface {
+    fn new(size: Size<u32>) -> Self {
+        Self { size, scale: 1 }
+    }
+
+    /// Resizes the [`Surface`], returning whether its size changed.
+    fn resize(&mut self, size: Size<u32>) -> bool {
+        let resized = self.size != size;
+        self.size = size;
+
+        resized
+    }
+
+    fn physical_size(self) -> Size<u32> {
+        Size::new(self.size.width * self.scale, self.size.height * self.scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_configures_resize_once() {
+        let mut surface = Surface::new(Size::new(400, 300));
+
+        let resizes = [Size::new(800, 600), Size::new(800, 600)]
+            .into_iter()
+            .filter(|&size| surface.resize(size))
+            .count();
+
+        assert_eq!(resizes, 1);
+        assert_eq!(surface.physical_size(), Size::new(800, 600));
+    }
+
+    #[test]
+    fn snapped_viewports_cover_whole_device_pixels() {
+        let surface = Surface {
+            size: Size::new(101, 75),
+            scale: 2,
+        };
+
+        let exact = viewport(surface, 1.5, false);
+        let snapped = viewport(surface, 1.5, true);
+
+        assert_eq!(exact.physical_size(), Size::new(202, 150));
+        assert_eq!(snapped.physical_size(), Size::new(201, 150));
+        assert_eq!(snapped.logical_size(), Size::new(67.0, 50.0));
     }
 }
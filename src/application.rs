@@ -33,6 +33,7 @@
 use crate::program::{self, Program};
 use crate::shell;
 use crate::theme;
+use crate::wayland;
 use crate::window;
 use crate::{
     Element, Executor, Font, Result, Settings, Size, Subscription, Task,
@@ -151,6 +152,7 @@ where
             _renderer: PhantomData,
         },
         settings: Settings::default(),
+        wayland: wayland::Settings::default(),
         window: window::Settings::default(),
     }
 }
@@ -166,6 +168,7 @@ where
 pub struct Application<P: Program> {
     raw: P,
     settings: Settings,
+    wayland: wayland::Settings,
     window: window::Settings,
 }
 
@@ -189,7 +192,12 @@ impl<P: Program> Application<P> {
         #[cfg(any(not(feature = "debug"), target_arch = "wasm32"))]
         let program = self.raw;
 
-        Ok(shell::run(program, self.settings, Some(self.window))?)
+        Ok(shell::run(
+            program,
+            self.settings,
+            self.wayland,
+            Some(self.window),
+        )?)
     }
 
     /// Sets the [`Settings`] that will be used to run the [`Application`].
@@ -197,6 +205,12 @@ impl<P: Program> Application<P> {
         Self { settings, ..self }
     }
 
+    /// Sets the [`wayland::Settings`] that will be used to run the
+    /// [`Application`].
+    pub fn wayland(self, wayland: wayland::Settings) -> Self {
+        Self { wayland, ..self }
+    }
+
     /// Sets the [`Settings::antialiasing`] of the [`Application`].
     pub fn antialiasing(self, antialiasing: bool) -> Self {
         Self {
@@ -332,6 +346,7 @@ impl<P: Program> Application<P> {
                 debug::hot(|| title.title(state))
             }),
             settings: self.settings,
+            wayland: self.wayland,
             window: self.window,
         }
     }
@@ -348,6 +363,7 @@ impl<P: Program> Application<P> {
                 debug::hot(|| f(state))
             }),
             settings: self.settings,
+            wayland: self.wayland,
             window: self.window,
         }
     }
@@ -364,6 +380,7 @@ impl<P: Program> Application<P> {
                 debug::hot(|| f(state))
             }),
             settings: self.settings,
+            wayland: self.wayland,
             window: self.window,
         }
     }
@@ -380,6 +397,7 @@ impl<P: Program> Application<P> {
                 debug::hot(|| f(state, theme))
             }),
             settings: self.settings,
+            wayland: self.wayland,
             window: self.window,
         }
     }
@@ -396,6 +414,7 @@ impl<P: Program> Application<P> {
                 debug::hot(|| f(state))
             }),
             settings: self.settings,
+            wayland: self.wayland,
             window: self.window,
         }
     }
@@ -412,6 +431,7 @@ impl<P: Program> Application<P> {
         Application {
             raw: program::with_executor::<P, E>(self.raw),
             settings: self.settings,
+            wayland: self.wayland,
             window: self.window,
         }
     }
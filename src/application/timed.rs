@@ -3,6 +3,7 @@ use crate::application::{Application, Boot, View};
 use crate::program;
 use crate::theme;
 use crate::time::Instant;
+use crate::wayland;
 use crate::window;
 use crate::{Element, Program, Settings, Subscription, Task};
 
@@ -142,6 +143,7 @@ where
             _renderer: PhantomData,
         },
         settings: Settings::default(),
+        wayland: wayland::Settings::default(),
         window: window::Settings::default(),
     }
 }
@@ -3,6 +3,7 @@ use crate::application;
 use crate::program::{self, Program};
 use crate::shell;
 use crate::theme;
+use crate::wayland;
 use crate::window;
 use crate::{Element, Executor, Font, Result, Settings, Subscription, Task};
 
@@ -97,6 +98,7 @@ where
             _renderer: PhantomData,
         },
         settings: Settings::default(),
+        wayland: wayland::Settings::default(),
     }
 }
 
@@ -111,6 +113,7 @@ where
 pub struct Daemon<P: Program> {
     raw: P,
     settings: Settings,
+    wayland: wayland::Settings,
 }
 
 impl<P: Program> Daemon<P> {
@@ -133,7 +136,7 @@ impl<P: Program> Daemon<P> {
         #[cfg(any(not(feature = "debug"), target_arch = "wasm32"))]
         let program = self.raw;
 
-        Ok(shell::run(program, self.settings, None)?)
+        Ok(shell::run(program, self.settings, self.wayland, None)?)
     }
 
     /// Sets the [`Settings`] that will be used to run the [`Daemon`].
@@ -141,6 +144,11 @@ impl<P: Program> Daemon<P> {
         Self { settings, ..self }
     }
 
+    /// Sets the [`wayland::Settings`] that will be used to run the [`Daemon`].
+    pub fn wayland(self, wayland: wayland::Settings) -> Self {
+        Self { wayland, ..self }
+    }
+
     /// Sets the [`Settings::antialiasing`] of the [`Daemon`].
     pub fn antialiasing(self, antialiasing: bool) -> Self {
         Self {
@@ -181,6 +189,7 @@ impl<P: Program> Daemon<P> {
                 debug::hot(|| title.title(state, window))
             }),
             settings: self.settings,
+            wayland: self.wayland,
         }
     }
 
@@ -196,6 +205,7 @@ impl<P: Program> Daemon<P> {
                 debug::hot(|| f(state))
             }),
             settings: self.settings,
+            wayland: self.wayland,
         }
     }
 
@@ -211,6 +221,7 @@ impl<P: Program> Daemon<P> {
                 debug::hot(|| f(state, window))
             }),
             settings: self.settings,
+            wayland: self.wayland,
         }
     }
 
@@ -226,6 +237,7 @@ impl<P: Program> Daemon<P> {
                 debug::hot(|| f(state, theme))
             }),
             settings: self.settings,
+            wayland: self.wayland,
         }
     }
 
@@ -241,6 +253,7 @@ impl<P: Program> Daemon<P> {
                 debug::hot(|| f(state, window))
             }),
             settings: self.settings,
+            wayland: self.wayland,
         }
     }
 
@@ -256,6 +269,7 @@ impl<P: Program> Daemon<P> {
         Daemon {
             raw: program::with_executor::<P, E>(self.raw),
             settings: self.settings,
+            wayland: self.wayland,
         }
     }
 }
@@ -12,6 +12,22 @@ pub enum Error {
     /// The application graphics context could not be created.
     #[error("the application graphics context could not be created")]
     GraphicsCreationFailed(graphics::Error),
+
+    /// The software renderer needs a shared memory format the compositor
+    /// does not support.
+    #[error("the compositor does not support the {0:?} shared memory format")]
+    UnsupportedShmFormat(shell::ShmFormat),
+
+    /// The compositor does not support layer surfaces, which every surface
+    /// is opened as.
+    #[error(
+        "the compositor does not support layer surfaces (zwlr_layer_shell_v1)"
+    )]
+    LayerShellUnsupported,
+
+    /// The connection to the compositor was lost.
+    #[error("the connection to the compositor was lost")]
+    ConnectionLost,
 }
 
 impl From<shell::Error> for Error {
@@ -23,6 +39,11 @@ impl From<shell::Error> for Error {
             shell::Error::GraphicsCreationFailed(error) => {
                 Error::GraphicsCreationFailed(error)
             }
+            shell::Error::UnsupportedShmFormat(format) => {
+                Error::UnsupportedShmFormat(format)
+            }
+            shell::Error::LayerShellUnsupported => Error::LayerShellUnsupported,
+            shell::Error::ConnectionLost => Error::ConnectionLost,
         }
     }
 }
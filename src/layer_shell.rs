@@ -2,3 +2,4 @@
 
 pub use crate::core::layer_shell::*;
 pub use crate::runtime::layer_shell::*;
+pub use crate::shell::layer_shell::*;
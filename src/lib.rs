@@ -547,11 +547,32 @@ pub mod task {
     pub use crate::runtime::task::{Never, Sipper, Straw, sipper, stream};
 }
 
+pub mod capabilities {
+    //! Query the capabilities of the compositor.
+    pub use crate::shell::capabilities::*;
+}
+
 pub mod clipboard {
     //! Access the clipboard.
     pub use crate::runtime::clipboard::{
         read, read_primary, write, write_primary,
     };
+
+    use crate::Task;
+
+    /// Writes the given contents to both the standard clipboard and the
+    /// primary selection, like an explicit copy of selected text.
+    ///
+    /// Each selection is offered through its own data source, since they are
+    /// owned by different devices. Both are set in the same update, so they
+    /// use the serial of the same input event and the sources of other
+    /// clients are replaced together.
+    pub fn write_all<T>(contents: String) -> Task<T>
+    where
+        T: Send + 'static,
+    {
+        Task::batch([write(contents.clone()), write_primary(contents)])
+    }
 }
 
 pub mod executor {
@@ -564,6 +585,7 @@ pub mod font {
     //! Load and use fonts.
     pub use crate::core::font::*;
     pub use crate::runtime::font::*;
+    pub use crate::shell::font::{families, is_available};
 }
 
 pub mod event {
@@ -619,6 +641,31 @@ pub mod touch {
     pub use crate::core::touch::{Event, Finger};
 }
 
+#[cfg(feature = "dmabuf-feedback")]
+pub mod dmabuf {
+    //! Find the buffer formats that a surface can be scanned out with.
+    pub use crate::shell::dmabuf::*;
+}
+
+pub mod subsurface {
+    //! Embed separately composited content within a window.
+    pub use crate::shell::subsurface::*;
+}
+
+pub mod wayland {
+    //! Configure the Wayland shell of your application.
+    pub use crate::shell::Settings;
+    pub use crate::shell::dnd;
+    pub use crate::shell::graphics::Antialiasing;
+    pub use crate::shell::input_method;
+    pub use crate::shell::keysym;
+    pub use crate::shell::pointer;
+    pub use crate::shell::renderer;
+    pub use crate::shell::selection;
+    pub use crate::shell::touch;
+    pub use crate::shell::trace;
+}
+
 #[allow(hidden_glob_reexports)]
 pub mod widget {
     //! Use the built-in widgets or create your own.
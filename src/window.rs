@@ -1,8 +1,11 @@
 //! Configure the window of your application in native platforms.
 
+pub mod decoration;
 pub mod icon;
 
+pub use decoration::Decoration;
 pub use icon::Icon;
 
 pub use crate::core::window::*;
 pub use crate::runtime::window::*;
+pub use crate::shell::surface::*;
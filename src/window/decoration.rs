@@ -0,0 +1,112 @@
+//! Draw a default decoration around the contents of a surface.
+//!
+//! The shell only creates layer surfaces, which compositors never decorate.
+//! A floating surface (e.g. a launcher or a notification) that wants to look
+//! like a window has to draw its own frame, which a [`Decoration`] provides:
+//! a background with rounded corners and a drop shadow, within a transparent
+//! margin that does not receive input.
+//!
+//! The style of the program must have a transparent background for the
+//! shadow and the corners to blend with whatever is behind the surface.
+use crate::core::window::Id;
+use crate::widget::container;
+use crate::{
+    Border, Color, Element, Length, Padding, Shadow, Task, Theme, Vector,
+};
+
+/// A shadow and a rounded frame drawn around the contents of a surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decoration {
+    /// The radius of the corners of the frame.
+    pub radius: f32,
+
+    /// The drop shadow of the frame.
+    pub shadow: Shadow,
+}
+
+impl Default for Decoration {
+    fn default() -> Self {
+        Self {
+            radius: 12.0,
+            shadow: Shadow {
+                color: Color::from_rgba(0.0, 0.0, 0.0, 0.35),
+                offset: Vector::new(0.0, 4.0),
+                blur_radius: 16.0,
+            },
+        }
+    }
+}
+
+impl Decoration {
+    /// Returns the transparent margin around the frame, which is large enough
+    /// to fit the shadow on every side.
+    pub fn margin(&self) -> Padding {
+        let blur = self.shadow.blur_radius.max(0.0);
+        let offset = self.shadow.offset;
+
+        Padding {
+            top: (blur - offset.y).max(0.0).ceil(),
+            right: (blur + offset.x).max(0.0).ceil(),
+            bottom: (blur + offset.y).max(0.0).ceil(),
+            left: (blur - offset.x).max(0.0).ceil(),
+        }
+    }
+
+    /// Draws the frame around the given contents, filling the surface.
+    ///
+    /// The frame uses the background color of the [`Theme`].
+    pub fn frame<'a, Message: 'a>(
+        self,
+        content: impl Into<Element<'a, Message>>,
+    ) -> Element<'a, Message> {
+        let frame = container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .clip(true)
+            .style(move |theme: &Theme| container::Style {
+                background: Some(theme.palette().background.into()),
+                border: Border::default().rounded(self.radius),
+                shadow: self.shadow,
+                ..container::Style::default()
+            });
+
+        container(frame)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .padding(self.margin())
+            .into()
+    }
+
+    /// Excludes the margin of the frame from the input region of the surface
+    /// with the given [`Id`], so clicks on the shadow go to whatever is behind
+    /// it.
+    ///
+    /// The corners of the frame still receive input, since the input region
+    /// is rectangular.
+    pub fn exclude_margin<T>(&self, id: Id) -> Task<T>
+    where
+        T: Send + 'static,
+    {
+        crate::shell::surface::set_input_margin(id, Some(self.margin()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn margin_fits_the_offset_shadow() {
+        let margin = Decoration::default().margin();
+
+        assert_eq!(
+            margin,
+            Padding {
+                top: 12.0,
+                right: 16.0,
+                bottom: 20.0,
+                left: 16.0,
+            }
+        );
+    }
+}